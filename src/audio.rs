@@ -0,0 +1,113 @@
+//! Things worth making a sound for. The world queues them up as they happen, and the
+//! host drains the queue every frame (`World::drain_audio_events`) to play them, or
+//! renders them offline.
+//!
+//! Times are simulation time and positions are in world units, so the host can delay
+//! and pan them however it likes.
+
+use crate::shells::ShellId;
+
+/// If nobody drains the queue, we stop adding to it at this point
+pub const MAX_QUEUED: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sound {
+    Launch { size: f64 }, // out of the mortar, `size` is the shell's radius
+    Whistle { duration: f64 }, // motor burning, in seconds
+    Burst {
+        shell: ShellId,
+        size: f64, // radius of the shell that burst
+        stars: usize,
+        energy: f64, // kinetic energy of all its stars, about ½ m v² with the shell at m = 1
+    },
+    Crackle { stars: usize, duration: f64 }, // glittering microstars going off for a while
+    Arrival { count: usize }, // lerpers got to their spots in the text, `count` of them in the same step
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioEvent {
+    pub time: u64, // microsseconds of simulation time
+    pub x: f64,
+    pub y: f64,
+    pub sound: Sound,
+}
+
+impl AudioEvent {
+    /// How many numbers `to_array` makes
+    pub const STRIDE: usize = 8;
+
+    /// Flat version for JS: `[time (ms), kind, x, y, shell, a, b, c]`, where kind is
+    /// 0 launch (a = size), 1 whistle (a = duration), 2 burst (a = size, b = stars,
+    /// c = energy), 3 crackle (a = stars, b = duration) or 4 arrival (a = count).
+    /// `shell` is -1 for anything that isn't a burst
+    pub fn to_array(&self) -> [f64; Self::STRIDE] {
+        let time = self.time as f64 / 1_000.;
+        let (kind, shell, a, b, c) = match self.sound {
+            Sound::Launch { size } => (0., -1., size, 0., 0.),
+            Sound::Whistle { duration } => (1., -1., duration, 0., 0.),
+            Sound::Burst { shell, size, stars, energy } => (2., shell as f64, size, stars as f64, energy),
+            Sound::Crackle { stars, duration } => (3., -1., stars as f64, duration, 0.),
+            Sound::Arrival { count } => (4., -1., count as f64, 0., 0.),
+        };
+        [time, kind, self.x, self.y, shell, a, b, c]
+    }
+
+    /// Back from `to_array`, or `None` if it doesn't look like one
+    pub fn from_array(a: &[f64]) -> Option<Self> {
+        let &[time, kind, x, y, shell, a, b, c] = a else {
+            return None;
+        };
+        let sound = match kind as i32 {
+            0 => Sound::Launch { size: a },
+            1 => Sound::Whistle { duration: a },
+            2 => Sound::Burst { shell: shell as ShellId, size: a, stars: b as usize, energy: c },
+            3 => Sound::Crackle { stars: a as usize, duration: b },
+            4 => Sound::Arrival { count: a as usize },
+            _ => return None,
+        };
+        Some(Self { time: (time * 1_000.).round() as u64, x, y, sound })
+    }
+}
+
+#[derive(Default)]
+pub struct AudioQueue {
+    events: Vec<AudioEvent>,
+}
+
+impl AudioQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, time: u64, x: f64, y: f64, sound: Sound) {
+        // every lerper in a countdown text lands in the same step, and that's one sound,
+        // not hundreds. They get merged into one event in the middle of them all
+        if let Sound::Arrival { count: n } = sound {
+            for e in self.events.iter_mut().rev().take_while(|e| e.time == time) {
+                if let Sound::Arrival { count } = &mut e.sound {
+                    let total = (*count + n) as f64;
+                    e.x = (e.x * *count as f64 + x * n as f64) / total;
+                    e.y = (e.y * *count as f64 + y * n as f64) / total;
+                    *count += n;
+                    return;
+                }
+            }
+        }
+        if self.events.len() < MAX_QUEUED {
+            self.events.push(AudioEvent { time, x, y, sound });
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Everything since the last drain, oldest first
+    pub fn drain(&mut self) -> Vec<AudioEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
@@ -0,0 +1,160 @@
+//! Renders a fireworks show offline, frame by frame.
+//!
+//!     cargo run --release --bin export -- --width 1920 --height 1080 --seed 2022 \
+//!         --duration 60 --fps 60 --format y4m --out show.y4m
+//!
+//! Then `ffmpeg -i show.y4m -i show.wav show.mp4` (or `ffmpeg -framerate 60 -i frames/%05d.png
+//! -i frames.wav ...`). The soundtrack is written in the same pass, next to the frames, unless
+//! `--audio` says where. Pass `--show shows/example.json` to render a scripted show instead of
+//! a random one
+
+use fireworks_2021_wasm::World;
+use fireworks_2021_wasm::export::{self, Y4mWriter};
+use fireworks_2021_wasm::raster::RasterRenderer;
+use fireworks_2021_wasm::synth::Mixer;
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+const USAGE: &str = "usage: export [--width W] [--height H] [--seed S] [--duration SECONDS] \
+[--fps FPS] [--countdown SECONDS] [--show FILE] [--format png|y4m] [--out PATH] [--audio FILE] \
+[--sample-rate HZ]";
+
+enum Format {
+    Png,
+    Y4m,
+}
+
+struct Options {
+    width: usize,
+    height: usize,
+    seed: u32,
+    duration: f64,
+    fps: u32,
+    countdown: f64, // seconds of countdown before the show proper
+    show: Option<PathBuf>,
+    format: Format,
+    out: Option<PathBuf>,
+    audio: Option<PathBuf>,
+    sample_rate: u32,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut opts = Options {
+        width: 1280,
+        height: 720,
+        seed: 2022,
+        duration: 30.,
+        fps: 60,
+        countdown: 0.,
+        show: None,
+        format: Format::Png,
+        out: None,
+        audio: None,
+        sample_rate: 48_000,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", flag))?;
+        let bad = || format!("bad value for {}: {}", flag, value);
+        match flag.as_str() {
+            "--width" => opts.width = value.parse().map_err(|_| bad())?,
+            "--height" => opts.height = value.parse().map_err(|_| bad())?,
+            "--seed" => opts.seed = value.parse().map_err(|_| bad())?,
+            "--duration" => opts.duration = value.parse().map_err(|_| bad())?,
+            "--fps" => opts.fps = value.parse().map_err(|_| bad())?,
+            "--countdown" => opts.countdown = value.parse().map_err(|_| bad())?,
+            "--format" => opts.format = match value.as_str() {
+                "png" => Format::Png,
+                "y4m" => Format::Y4m,
+                _ => return Err(format!("unknown format {}", value)),
+            },
+            "--show" => opts.show = Some(PathBuf::from(value)),
+            "--out" => opts.out = Some(PathBuf::from(value)),
+            "--audio" => opts.audio = Some(PathBuf::from(value)),
+            "--sample-rate" => opts.sample_rate = value.parse().map_err(|_| bad())?,
+            _ => return Err(format!("unknown flag {}", flag)),
+        }
+    }
+
+    if opts.width == 0 || opts.height == 0 || opts.fps == 0 || opts.sample_rate == 0 {
+        return Err("width, height, fps and sample rate must be positive".to_string());
+    }
+    Ok(opts)
+}
+
+fn main() -> io::Result<()> {
+    let opts = match parse_args() {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let renderer = RasterRenderer::new(opts.width, opts.height);
+    let mut world = World::with_renderer(opts.width as f64, opts.height as f64, opts.seed, Box::new(renderer));
+
+    if let Some(path) = &opts.show {
+        let src = fs::read_to_string(path)?;
+        match world.load_show(0., &src) {
+            Ok(n) => eprintln!("loaded {} cues from {}", n, path.display()),
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut y4m = None;
+    let out = match opts.format {
+        Format::Png => {
+            let dir = opts.out.clone().unwrap_or_else(|| PathBuf::from("frames"));
+            fs::create_dir_all(&dir)?;
+            dir
+        }
+        Format::Y4m => {
+            let path = opts.out.clone().unwrap_or_else(|| PathBuf::from("show.y4m"));
+            let file = BufWriter::new(File::create(&path)?);
+            y4m = Some(Y4mWriter::new(file, opts.width, opts.height, opts.fps)?);
+            path
+        }
+    };
+
+    let audio_path = opts.audio.clone().unwrap_or_else(|| out.with_extension("wav"));
+    let mut mixer = Mixer::new(opts.sample_rate as f64, opts.seed, world.world_width(), world.world_height());
+
+    let frames = (opts.duration * opts.fps as f64).round() as u64;
+    for frame in 0..frames {
+        let time = frame as f64 * 1000. / opts.fps as f64; // ms, like performance.now()
+        world.update_countdown(time, opts.countdown - time / 1000.);
+        world.loopity_loop(time);
+        for event in world.audio_events() {
+            mixer.add(&event);
+        }
+
+        let pixels = world.frame().expect("raster renderer always has a frame");
+        match &mut y4m {
+            Some(w) => w.write_frame(pixels)?,
+            None => export::write_png(&out.join(format!("{:05}.png", frame)), opts.width, opts.height, pixels)?,
+        }
+
+        if frame % opts.fps as u64 == 0 {
+            eprintln!("{}s / {}s, {} entities", frame / opts.fps as u64, opts.duration, world.entity_count());
+        }
+    }
+
+    // dropping would flush too, but it'd swallow the error of the last write
+    y4m.map(|w| w.into_inner().flush()).transpose()?;
+
+    // exactly as long as the video, so they stay lined up when muxed
+    let samples = mixer.finish(frames as f64 / opts.fps as f64);
+    let mut wav = BufWriter::new(File::create(&audio_path)?);
+    export::write_wav(&mut wav, opts.sample_rate, 2, &samples)?;
+    wav.flush()?;
+
+    eprintln!("wrote {} frames to {} and the soundtrack to {}", frames, out.display(), audio_path.display());
+    Ok(())
+}
@@ -1,10 +1,34 @@
-use wasm_bindgen::JsValue;
-use web_sys::CanvasRenderingContext2d;
-use js_sys::Math;
+use crate::rng::Rng;
+use crate::shells::ShellId;
+use crate::utils::Color;
+use crate::wind::Air;
 
 /// Gravity acceleration thingy
 const G: f64 = 500.0;
 
+/// Drag coefficient of a plain star, per second: velocity goes `*= exp(-k * dt)`. This
+/// used to be `*= 0.95` every frame at 45fps, and -45 ln(0.95) is about 2.31
+const PARTICLE_DRAG: f64 = 2.31;
+
+/// Heavy stars (willows) barely slow down, so they keep falling and hang in the air
+const MASSIVE_DRAG: f64 = 0.6;
+
+/// Stars this big get exactly their behaviour's drag. Air resistance goes with the
+/// area (r²) and inertia with the volume (r³), so smaller stars slow down faster.
+/// `m` doesn't come into it, it's really just a gravity multiplier
+const DRAG_RADIUS: f64 = 1.5;
+
+/// How long a motor with `thrust` has to burn so that a rocket leaving the mortar at
+/// `v0` (straight up, with `m = 1`) tops out `height` above where it started
+pub fn burn_for_apogee(v0: f64, thrust: f64, height: f64) -> f64 {
+    // climb while burning for t, plus the coast at the end: v0 t + a t²/2 + (v0 + a t)²/2G
+    let a = thrust - G;
+    let qa = a / 2. + a * a / (2. * G);
+    let qb = v0 + v0 * a / G;
+    let qc = v0 * v0 / (2. * G) - height;
+    ((-qb + (qb * qb - 4. * qa * qc).sqrt()) / (2. * qa)).max(0.)
+}
+
 fn qlerp(from: f64, to: f64, perc: f64) -> f64 {
     let y = - (perc - 1.) * (perc - 1.) + 1.; // quadratic thingy from 0 to 1
     from * (1. - y) + to * y // linear lerp
@@ -18,60 +42,14 @@ pub struct Body {
     pub vy: f64,
     pub m: f64,
     pub r: f64,
-    pub color: JsValue,
+    pub color: Color,
 }
 
 impl Body {
-    // TODO: is the acceleration constant, or does the firework accelerate up a bit?
-    #[inline]
-    pub fn update(&mut self, behaviour: &Behaviour, dt: f64, time: u64) {
-        self.x += self.vx * dt;
-
-        let a = G * self.m; // I know this is not how it works, just pretend it does because of air resistence or something
-        self.y += self.vy * dt + 0.5 * a * dt * dt;
-        self.vy += a * dt;
-
-        use Behaviour::*;
-        match behaviour {
-            Particle | Glitter(_) | HueParticle(_, _) => {
-                self.vx *= 0.95;
-                self.vy *= 0.95;
-            }
-            _ => {}
-        }
-
-        match behaviour {
-            Glitter(phi) => {
-                self.r = (time as f64 / 1_000_000. * phi).sin().max(0.) * 1.2;
-            }
-
-            HueParticle(hue, freq) => {
-                let x = hue + time as f64 / 1_000_000. * 360. * freq;
-                self.color = JsValue::from_str(&format!("hsl({:.2},85%,75%)", x));
-            }
-
-            UserLerper(info) | Lerper(info) => {
-                let p = (time - info.it) as f64 / (info.tt - info.it) as f64;
-                self.x = qlerp(info.ix, info.tx, p);
-                self.y = qlerp(info.iy, info.ty, p);
-            }
-
-            _ => {}
-        }
-    }
-
-    #[inline]
-    pub fn draw(&self, ctx: &CanvasRenderingContext2d) {
-        ctx.begin_path();
-        ctx.set_fill_style(&self.color);
-        ctx.arc(self.x, self.y, self.r, 0.0, std::f64::consts::PI * 2.0).unwrap();
-        ctx.fill();
-    }
-
     #[inline]
-    pub fn plus_explosion(&self, divisions: i32, force_base: f64) -> Body {
-        let theta = Math::random() * std::f64::consts::PI * 2.;
-        let u = Math::random(); // uniform random. we'll modify the distribution in `r`
+    pub fn plus_explosion(&self, rng: &mut Rng, divisions: i32, force_base: f64) -> Body {
+        let theta = rng.random() * std::f64::consts::PI * 2.;
+        let u = rng.random(); // uniform random. we'll modify the distribution in `r`
         let r = (u - 1.) * (u - 1.) * (u - 1.) + 1.; // dude this distribution is FUCKING AMAZING
                                                      // I LOVE IT
                                                      // the fireworks become so much ROUNDER AND STUFF
@@ -81,42 +59,34 @@ impl Body {
             vx: theta.cos() * force + self.vx * 0.2,
             vy: theta.sin() * force + self.vy * 0.2,
             m: self.m / divisions as f64,
-            color: self.color.clone(),
             ..*self
         }
     }
 
     #[inline]
-    pub fn plus_directed_explosion(&self, divisions: i32, force_base: f64) -> Body {
-        let theta = (Math::random() - 0.5) * std::f64::consts::PI * 0.5;
-        let theta = theta + Math::atan2(self.vy, self.vx);
-        let force = Math::random() * force_base;
+    pub fn plus_directed_explosion(&self, rng: &mut Rng, divisions: i32, force_base: f64) -> Body {
+        let theta = (rng.random() - 0.5) * std::f64::consts::PI * 0.5;
+        let theta = theta + self.vy.atan2(self.vx);
+        let force = rng.random() * force_base;
         Body { 
-            vx: Math::cos(theta) * force + self.vx * 0.3,
-            vy: Math::sin(theta) * force + self.vy * 0.3,
+            vx: theta.cos() * force + self.vx * 0.3,
+            vy: theta.sin() * force + self.vy * 0.3,
             m: self.m / divisions as f64,
-            color: self.color.clone(),
             ..*self
         }
     }
 
     #[inline]
-    pub fn plus_willow_explosion(&self, force_base: f64) -> Body {
-        let theta = Math::random() * std::f64::consts::PI * 2.;
-        let force = Math::random() * force_base;
+    pub fn plus_willow_explosion(&self, rng: &mut Rng, force_base: f64) -> Body {
+        let theta = rng.random() * std::f64::consts::PI * 2.;
+        let force = rng.random() * force_base;
         Body { 
             vx: theta.cos() * force * 0.35 + self.vx * 0.2,
             vy: (theta.sin() - 1.) * 0.2 * force + self.vy * 0.2,
             m: self.m * 0.7,
-            color: self.color.clone(),
             ..*self
         }
     }
-
-    #[inline]
-    pub fn with_color(&self, color: JsValue) -> Body {
-        Body { color, ..*self }
-    }
 }
 
 #[derive(Clone, Copy)]
@@ -135,31 +105,273 @@ impl LerperInfo {
     }
 }
 
+/// What the update loop needs to know about a behaviour, in one byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Tag {
+    Plain, // just physics
+    Glitter,
+    Hue,
+    Lerper,
+}
+
+impl Behaviour {
+    #[inline]
+    pub fn tag(&self) -> Tag {
+        use Behaviour::*;
+        match self {
+            Glitter(_) => Tag::Glitter,
+            HueParticle(_, _) => Tag::Hue,
+            Lerper(_) | UserLerper(_) => Tag::Lerper,
+            _ => Tag::Plain,
+        }
+    }
+
+    /// Drag coefficient per second, for a star of `DRAG_RADIUS` in air of density 1.
+    /// Shells, comets and lerpers fly on rails, or close enough
+    #[inline]
+    pub fn drag(&self) -> f64 {
+        use Behaviour::*;
+        match self {
+            Particle | Glitter(_) | HueParticle(_, _) => PARTICLE_DRAG,
+            MassiveParticle => MASSIVE_DRAG,
+            _ => 0.,
+        }
+    }
+
+    /// `drag` for a body of radius `r`
+    #[inline]
+    pub fn drag_for(&self, r: f64) -> f64 {
+        self.drag() * (DRAG_RADIUS / r).clamp(0.5, 2.)
+    }
+}
+
+/// Every body in the world, as a structure of arrays, so the physics can run as
+/// tight loops over plain `f64` slices (which LLVM vectorizes, simd128 on wasm).
+/// Index `i` of every array belongs to the same entity
+#[derive(Default)]
+pub struct Bodies {
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub vx: Vec<f64>,
+    pub vy: Vec<f64>,
+    pub m: Vec<f64>,
+    pub r: Vec<f64>,
+    pub drag: Vec<f64>, // see `Behaviour::drag_for`
+    pub fade: Vec<f64>, // how much velocity (relative to the air) is left after a step of drag
+    pub thrust: Vec<f64>, // motor acceleration along the direction of flight, units/s²
+    pub burn: Vec<f64>, // seconds of motor left
+    pub launched: Vec<bool>, // went up from the ground, instead of coming out of a burst
+    pub px: Vec<f64>, // where each body was before the last step, for interpolation
+    pub py: Vec<f64>,
+    pub tag: Vec<Tag>,
+    pub color: Vec<Color>,
+    air: f64, // density times the step length, see `set_air`
+}
+
+impl Bodies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn push(&mut self, body: Body, behaviour: &Behaviour) {
+        self.x.push(body.x);
+        self.y.push(body.y);
+        self.vx.push(body.vx);
+        self.vy.push(body.vy);
+        self.m.push(body.m);
+        self.r.push(body.r);
+        self.thrust.push(0.);
+        self.burn.push(0.);
+        self.launched.push(false);
+        self.px.push(body.x);
+        self.py.push(body.y);
+        self.color.push(body.color);
+        let k = behaviour.drag_for(body.r);
+        self.drag.push(k);
+        self.fade.push((-k * self.air).exp());
+        self.tag.push(behaviour.tag());
+    }
+
+    pub fn swap_remove(&mut self, i: usize) {
+        self.x.swap_remove(i);
+        self.y.swap_remove(i);
+        self.vx.swap_remove(i);
+        self.vy.swap_remove(i);
+        self.m.swap_remove(i);
+        self.r.swap_remove(i);
+        self.thrust.swap_remove(i);
+        self.burn.swap_remove(i);
+        self.launched.swap_remove(i);
+        self.px.swap_remove(i);
+        self.py.swap_remove(i);
+        self.color.swap_remove(i);
+        self.drag.swap_remove(i);
+        self.fade.swap_remove(i);
+        self.tag.swap_remove(i);
+    }
+
+    /// Copies body `i` out of the arrays
+    pub fn get(&self, i: usize) -> Body {
+        Body {
+            x: self.x[i],
+            y: self.y[i],
+            vx: self.vx[i],
+            vy: self.vy[i],
+            m: self.m[i],
+            r: self.r[i],
+            color: self.color[i],
+        }
+    }
+
+    /// Writes `body` over body `i`, keeping its previous position
+    pub fn set(&mut self, i: usize, body: Body) {
+        self.x[i] = body.x;
+        self.y[i] = body.y;
+        self.vx[i] = body.vx;
+        self.vy[i] = body.vy;
+        self.m[i] = body.m;
+        self.r[i] = body.r;
+        self.color[i] = body.color;
+    }
+
+    pub fn set_behaviour(&mut self, i: usize, behaviour: &Behaviour) {
+        self.drag[i] = behaviour.drag_for(self.r[i]);
+        self.fade[i] = (-self.drag[i] * self.air).exp();
+        self.tag[i] = behaviour.tag();
+    }
+
+    /// Air of `density`, and steps of `dt` seconds from now on. `integrate` only
+    /// applies drag right for that `dt`, so the `exp` stays out of its loop
+    pub fn set_air(&mut self, density: f64, dt: f64) {
+        self.air = density * dt;
+        for (f, k) in self.fade.iter_mut().zip(self.drag.iter()) {
+            *f = (-k * self.air).exp();
+        }
+    }
+
+    /// Starts body `i`'s motor: `thrust` units/s² for `burn` seconds
+    pub fn ignite(&mut self, i: usize, thrust: f64, burn: f64) {
+        self.thrust[i] = thrust;
+        self.burn[i] = burn;
+    }
+
+    /// Stretches every position by (`sx`, `sy`), for when the world changes shape
+    pub fn remap(&mut self, sx: f64, sy: f64) {
+        for x in self.x.iter_mut().chain(self.px.iter_mut()) {
+            *x *= sx;
+        }
+        for y in self.y.iter_mut().chain(self.py.iter_mut()) {
+            *y *= sy;
+        }
+    }
+
+    pub fn save_positions(&mut self) {
+        self.px.copy_from_slice(&self.x);
+        self.py.copy_from_slice(&self.y);
+    }
+
+    /// Motors, gravity, velocity, and drag towards moving with the wind, for everyone.
+    /// `dt` should be the one given to `set_air`, and `air.density` is already in `fade`
+    pub fn integrate(&mut self, dt: f64, air: &Air) {
+        // rockets push along wherever they're pointed, which is wherever they're going
+        for i in 0..self.len() {
+            if self.burn[i] <= 0. {
+                continue;
+            }
+            let t = self.burn[i].min(dt); // the motor can run out mid-step
+            let speed = (self.vx[i] * self.vx[i] + self.vy[i] * self.vy[i]).sqrt();
+            let (dx, dy) = if speed > 0. { (self.vx[i] / speed, self.vy[i] / speed) } else { (0., -1.) };
+            self.vx[i] += dx * self.thrust[i] * t;
+            self.vy[i] += dy * self.thrust[i] * t;
+            self.burn[i] -= dt;
+        }
+
+        for (x, vx) in self.x.iter_mut().zip(self.vx.iter()) {
+            *x += vx * dt;
+        }
+
+        for ((y, vy), m) in self.y.iter_mut().zip(self.vy.iter_mut()).zip(self.m.iter()) {
+            let a = G * m; // I know this is not how it works, just pretend it does because of air resistence or something
+            *y += *vy * dt + 0.5 * a * dt * dt;
+            *vy += a * dt;
+        }
+
+        // exact for any dt, so it doesn't matter how often we step. Without wind this
+        // is just v *= f. `Air::wind_at` is spelled out so it's all multiply-adds
+        let (wx, wy) = air.wind;
+        let (altitude, per_y) = (air.altitude, 1. / air.height);
+        let bodies = self.vx.iter_mut().zip(self.vy.iter_mut()).zip(self.fade.iter()).zip(self.y.iter());
+        for (((vx, vy), f), y) in bodies {
+            let up = (1. - y * per_y).clamp(0., 1.);
+            let scale = 1. + altitude * up;
+            let (wx, wy) = (wx * scale, wy * scale);
+            *vx = wx + (*vx - wx) * f;
+            *vy = wy + (*vy - wy) * f;
+        }
+    }
+
+    /// Everything that isn't plain physics: blinking, hue cycling and lerping.
+    /// `behaviours` is indexed just like the arrays
+    pub fn animate(&mut self, behaviours: &[Behaviour], time: u64) {
+        use Behaviour::*;
+        for (i, (tag, behaviour)) in self.tag.iter().zip(behaviours).enumerate() {
+            if *tag == Tag::Plain {
+                continue;
+            }
+
+            match behaviour {
+                Glitter(phi) => {
+                    self.r[i] = (time as f64 / 1_000_000. * phi).sin().max(0.) * 1.2;
+                }
+
+                HueParticle(hue, freq) => {
+                    let x = hue + time as f64 / 1_000_000. * 360. * freq;
+                    self.color[i] = Color::new(x, 85., 75.);
+                }
+
+                UserLerper(info) | Lerper(info) => {
+                    let p = time.saturating_sub(info.it) as f64 / (info.tt - info.it) as f64;
+                    self.x[i] = qlerp(info.ix, info.tx, p);
+                    self.y[i] = qlerp(info.iy, info.ty, p);
+                }
+
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A shell sitting in its mortar until its cue fires
+#[derive(Clone)]
+pub struct Launch {
+    pub vx: f64,
+    pub vy: f64,
+    pub r: f64,
+    pub thrust: f64, // see `Bodies::ignite`
+    pub burn: f64,
+    pub shell: Behaviour,
+    pub fuse: Option<u64>, // microsseconds from launch to burst, or `None` to burst at apogee
+}
+
 #[derive(Clone)]
 pub enum Behaviour {
     Particle, // just a body
     HueParticle(f64, f64), // changes hue with time (initial_hue, frequency)
     Glitter(f64), // a body that fades in and out
 
-    MassiveParticle, // particle with mass? doesn't have self.velocity *= 0.95 every frame
-    Chris, // Chrysanthemus
-    MultiColorChris,
-    Willow,
+    MassiveParticle, // particle with mass? way less drag than the others
+
+    Shell(ShellId), // bursts when its fuse runs out, see `shells.rs`
     Comet(i32), // i32 represents how many particles the commet will emit during it's lifespan
-    Crosette,
-    CrosetteBranch,
-    Pistil,
-    Peony,
-    Strobe,
 
     Lerper(Box<LerperInfo>), // box because I don't want to make the size of a lerper bigger ¯\_(ツ)_/¯
     UserLerper(Box<LerperInfo>),
-}
 
-pub fn random_behaviour() -> Behaviour {
-    use Behaviour::*;
-    (*crate::utils::choose_from(&[
-        Chris, MultiColorChris, Crosette, Pistil, Strobe, Peony, Willow,
-        Comet((Math::random() * 45. + 20.).floor() as i32),
-    ])).clone()
+    Mortar(Box<Launch>),
 }
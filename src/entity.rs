@@ -0,0 +1,89 @@
+//! Generational entity handles. A handle stays valid until its entity is removed,
+//! after which it never matches anything again, even if the slot gets reused
+
+pub const DEAD: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entity {
+    index: u32, // into `Entities::slots`
+    generation: u32,
+}
+
+impl Entity {
+    /// Sorts before every other handle, handy for range queries on ordered sets
+    pub const MIN: Entity = Entity { index: 0, generation: 0 };
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    generation: u32,
+    dense: u32, // index into the component arrays, or DEAD
+}
+
+/// Maps handles to indices in the (dense, swap-removed) component arrays
+#[derive(Default)]
+pub struct Entities {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    dense: Vec<Entity>, // component index -> entity
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    /// New entity at the end of the component arrays (index `len() - 1`)
+    pub fn spawn(&mut self) -> Entity {
+        let dense = self.dense.len() as u32;
+        let entity = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.dense = dense;
+                Entity { index, generation: slot.generation }
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, dense });
+                Entity { index: self.slots.len() as u32 - 1, generation: 0 }
+            }
+        };
+        self.dense.push(entity);
+        entity
+    }
+
+    /// Component index of `entity`, or None if it's dead
+    #[inline]
+    pub fn get(&self, entity: Entity) -> Option<usize> {
+        let slot = self.slots.get(entity.index as usize)?;
+        if slot.generation == entity.generation && slot.dense != DEAD {
+            Some(slot.dense as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Removes `entity` and returns the component index the caller has to
+    /// `swap_remove` from every component array, or None if it was already dead
+    pub fn despawn(&mut self, entity: Entity) -> Option<usize> {
+        let i = self.get(entity)?;
+
+        self.dense.swap_remove(i);
+        if let Some(moved) = self.dense.get(i) {
+            self.slots[moved.index as usize].dense = i as u32;
+        }
+
+        let slot = &mut self.slots[entity.index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.dense = DEAD;
+        self.free.push(entity.index);
+        Some(i)
+    }
+}
@@ -0,0 +1,94 @@
+//! Writing rendered frames (and sound) to disk, so shows can be recorded offline
+//! instead of screen captured. Native only
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// A straight alpha RGBA pixel over the black page background
+#[inline]
+fn over_black(px: &[u8]) -> [u8; 3] {
+    let a = px[3] as u32;
+    let f = |c: u8| ((c as u32 * a + 127) / 255) as u8;
+    [f(px[0]), f(px[1]), f(px[2])]
+}
+
+/// Writes an RGBA frame as an RGB png. Whatever is transparent ends up black,
+/// like the page background
+pub fn write_png(path: &Path, width: usize, height: usize, rgba: &[u8]) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(over_black).collect();
+    encoder
+        .write_header()
+        .and_then(|mut w| w.write_image_data(&rgb))
+        .map_err(io::Error::other)
+}
+
+/// A YUV4MPEG2 stream (4:4:4, BT.601 limited range), which ffmpeg reads directly:
+/// `ffmpeg -i show.y4m show.mp4`
+pub struct Y4mWriter<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+    planes: Vec<u8>,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(mut out: W, width: usize, height: usize, fps: u32) -> io::Result<Self> {
+        writeln!(out, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", width, height, fps)?;
+        Ok(Self {
+            out,
+            width,
+            height,
+            planes: vec![0; width * height * 3],
+        })
+    }
+
+    pub fn write_frame(&mut self, rgba: &[u8]) -> io::Result<()> {
+        let n = self.width * self.height;
+        let (y, uv) = self.planes.split_at_mut(n);
+        let (u, v) = uv.split_at_mut(n);
+        for (i, px) in rgba.chunks_exact(4).take(n).enumerate() {
+            let [r, g, b] = over_black(px).map(|c| c as i32);
+            y[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            u[i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            v[i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+        }
+
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&self.planes)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Writes interleaved samples in [-1, 1] as a 16 bit PCM wav, which everything plays
+/// and ffmpeg muxes: `ffmpeg -i show.y4m -i show.wav show.mp4`
+pub fn write_wav<W: Write>(mut out: W, sample_rate: u32, channels: u16, samples: &[f32]) -> io::Result<()> {
+    let data_len = (samples.len() * 2) as u32;
+    let block_align = channels * 2;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?; // size of the fmt chunk
+    out.write_all(&1u16.to_le_bytes())?; // integer PCM
+    out.write_all(&channels.to_le_bytes())?;
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * block_align as u32).to_le_bytes())?; // bytes per second
+    out.write_all(&block_align.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+
+    let pcm: Vec<u8> = samples
+        .iter()
+        .flat_map(|s| ((s.clamp(-1., 1.) * i16::MAX as f32).round() as i16).to_le_bytes())
+        .collect();
+    out.write_all(&pcm)
+}
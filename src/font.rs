@@ -65,11 +65,11 @@ impl Text {
     }
 
     pub fn build(&mut self) -> Vec<Point> {
-        std::mem::replace(&mut self.points, Vec::new())
+        std::mem::take(&mut self.points)
     }
 }
 
-const POINTS: [&'static [(f64, f64)]; 11] = [
+const POINTS: [&[(f64, f64)]; 11] = [
     // 0
     &[(0.5102040816326531, 0.027210884353741496),(0.46938775510204084, 0.027210884353741496),(0.41496598639455784, 0.034013605442176874),(0.36054421768707484, 0.05442176870748299),(0.29931972789115646, 0.08163265306122448),(0.23809523809523808, 0.12244897959183673),(0.1836734693877551, 0.17006802721088435),(0.14965986394557823, 0.20408163265306123),(0.1292517006802721, 0.23809523809523808),(0.10204081632653061, 0.272108843537415),(0.08163265306122448, 0.3197278911564626),(0.061224489795918366, 0.3673469387755102),(0.047619047619047616, 0.4217687074829932),(0.027210884353741496, 0.46938775510204084),(0.013605442176870748, 0.5170068027210885),(0.013605442176870748, 0.564625850340136),(0.006802721088435374, 0.6122448979591837),(0.0, 0.6530612244897959),(0.0, 0.7278911564625851),(0.013605442176870748, 0.7891156462585034),(0.047619047619047616, 0.8435374149659864),(0.09523809523809523, 0.8979591836734694),(0.14285714285714285, 0.9319727891156463),(0.20408163265306123, 0.9591836734693877),(0.23809523809523808, 0.9727891156462585),(0.29931972789115646, 0.9795918367346939),(0.3333333333333333, 0.9863945578231292),(0.3673469387755102, 0.9931972789115646),(0.4421768707482993, 1.0),(0.5102040816326531, 1.0),(0.5510204081632653, 1.0),(0.5918367346938775, 1.0),(0.6258503401360545, 0.9931972789115646),(0.6666666666666666, 0.9795918367346939),(0.7414965986394558, 0.9523809523809523),(0.7755102040816326, 0.9251700680272109),(0.8095238095238095, 0.9115646258503401),(0.8639455782312925, 0.8707482993197279),(0.891156462585034, 0.8435374149659864),(0.9319727891156463, 0.7959183673469388),(0.9727891156462585, 0.7482993197278912),(1.0, 0.6938775510204082),(1.0204081632653061, 0.6598639455782312),(1.0272108843537415, 0.6258503401360545),(1.0408163265306123, 0.5714285714285714),(1.0476190476190477, 0.5102040816326531),(1.0476190476190477, 0.4557823129251701),(1.034013605442177, 0.4013605442176871),(1.0136054421768708, 0.3469387755102041),(1.0, 0.3129251700680272),(0.9659863945578231, 0.2585034013605442),(0.9251700680272109, 0.20408163265306123),(0.8843537414965986, 0.1564625850340136),(0.8367346938775511, 0.12244897959183673),(0.7959183673469388, 0.08843537414965986),(0.7551020408163265, 0.061224489795918366),(0.7142857142857143, 0.034013605442176874),(0.673469387755102, 0.013605442176870748),(0.6394557823129252, 0.0),(0.5986394557823129, 0.0),(0.5578231292517006, 0.0),(0.5170068027210885, 0.0)],
 
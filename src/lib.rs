@@ -1,53 +1,332 @@
 pub mod utils;
-use utils::{HSL, random_color};
+use utils::Color;
+
+pub mod rng;
+use rng::Rng;
 
 mod components;
-use components::{Body, Behaviour, LerperInfo};
+use components::{Body, Bodies, Behaviour, LerperInfo, Launch};
 
 mod font;
 
+pub mod entity;
+use entity::{Entity, Entities};
+
+pub mod render;
+use render::{Renderer, CanvasRenderer, NullRenderer, Background};
+
+pub mod raster;
+use raster::RasterRenderer;
+
+pub mod show;
+use show::{Show, CueColor, CueFuse};
+
+pub mod shells;
+use shells::{ShellSpec, ShellId, Star, StarColor, Explosion};
+
+pub mod wind;
+use wind::Wind;
+
+pub mod smoke;
+use smoke::Smoke;
+
+pub mod audio;
+use audio::{AudioQueue, AudioEvent, Sound};
+
+pub mod synth;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod export;
+
 use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, console};
-use js_sys::{Math};
-use std::collections::{BTreeSet, BTreeMap};
+use web_sys::console;
+use js_sys::Math;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+/// Length of one simulation step, in microsseconds (120Hz)
+const STEP: u64 = 8_333;
+
+/// If the host falls behind by more than this (hidden tab, huge hitch), we just
+/// skip ahead instead of simulating every step we missed
+const MAX_CATCHUP: u64 = 250_000;
+
+/// The world is measured in units, not pixels: this box of them always fits the screen
+/// exactly on one side (and there's more world on the other side, depending on the
+/// aspect ratio). Everything else (gravity, shell sizes, the countdown text) is tuned
+/// for it, so the show looks the same at any resolution
+const VIEW_WIDTH: f64 = 1280.;
+const VIEW_HEIGHT: f64 = 720.;
+
+/// Average number of random shells launched per second after the countdown ends
+const LAUNCHES_PER_SECOND: f64 = 2.7;
+
+/// Default for `World::set_max_particles`. A few overlapping Pistils fit, a phone
+/// still keeps up
+const MAX_PARTICLES: usize = 8_000;
+
+/// Default for `World::set_frame_budget`, in milliseconds of our own work per frame
+const FRAME_BUDGET_MS: f64 = 12.;
+
+/// Quality never goes below this, or the show just stops being a show
+const MIN_QUALITY: f64 = 0.25;
+
+/// Motor acceleration of random shells, in units/s². Gravity takes away 500 of it
+const ROCKET_THRUST: f64 = 1300.;
+
+/// Sparks a burning motor throws out per second, on average
+const TAIL_SPARKS_PER_SECOND: f64 = 90.;
+
+/// Smoke puffs a rising shell leaves behind per second, on average
+const SHELL_SMOKE_PER_SECOND: f64 = 15.;
+
+/// Default for `World::set_trail_half_life`, in seconds. About what painting black at
+/// 0.2 alpha every frame at 45fps used to look like
+const TRAIL_HALF_LIFE: f64 = 0.07;
 
 
 #[wasm_bindgen]
 pub struct World {
-    width: f64, 
+    width: f64, // in world units
     height: f64,
-    ctx: CanvasRenderingContext2d,
+    scale: f64, // css pixels per world unit
+    pixel_ratio: f64, // device pixels per css pixel
+    renderer: Box<dyn Renderer>,
 
-    next_entity: u32,
-    entity_index: BTreeMap<u32, usize>,
-    entity_id: Vec<u32>,
-    bodies: Vec<Body>,
+    entities: Entities,
+    bodies: Bodies,
     behaviours: Vec<Behaviour>,
-    fuses: BTreeSet<(u64, u32)>, // (timestamp, entity id)
+    shells: Vec<Rc<ShellSpec>>, // indexed by ShellId
+    fuses: BTreeSet<(u64, Entity)>, // (timestamp, entity)
+    apogee_fuses: Vec<Entity>, // fire once the entity stops going up
 
     countdown: i32,
+    random_launches: bool, // after the countdown ends
+
+    max_particles: usize,
+    frame_budget: f64, // ms
+    frame_ms: f64, // smoothed frame time reported by the host
+    quality: f64, // in [MIN_QUALITY, 1], scales bursts, launches and trails
+
+    time: u64, // simulation clock, in microsseconds
+    air_density: f64, // multiplies every drag coefficient, 1 is normal
+    wind: Wind,
+    smoke: Smoke,
+    smoky: bool, // whether bursts and shells make new smoke
+    audio: AudioQueue, // for the host to drain
+
+    background: Background,
+    trail_half_life: f64, // seconds, 0 clears every frame
+    last_draw: u64, // host time of the last `draw`, in microsseconds
+
+    seed: u32,
+    rng: Rng,
+
+    // scratch space for `draw`, kept around so we don't allocate every frame
+    draw_order: Vec<(u32, u32)>, // (color key, body index)
+    batch: Vec<(f64, f64, f64)>,
 }
 
-#[wasm_bindgen]
 impl World {
-    pub fn new(width: f64, height: f64) -> Self {
-        Self {
-            width,
-            height,
-            ctx: utils::get_context("world"),
-            next_entity: 0,
-            entity_index: BTreeMap::new(),
-            entity_id: Vec::new(),
-            bodies: Vec::new(),
+    /// Same as `World::with_seed`, but draws with any backend instead of the
+    /// "world" canvas
+    pub fn with_renderer(width: f64, height: f64, seed: u32, renderer: Box<dyn Renderer>) -> Self {
+        let mut world = Self {
+            width: VIEW_WIDTH,
+            height: VIEW_HEIGHT,
+            scale: 1.,
+            pixel_ratio: 1.,
+            renderer,
+            entities: Entities::new(),
+            bodies: Bodies::new(),
             behaviours: Vec::new(),
+            shells: shells::builtin(),
             fuses: BTreeSet::new(),
+            apogee_fuses: Vec::new(),
             countdown: -1,
-        }
+            random_launches: true,
+            max_particles: MAX_PARTICLES,
+            frame_budget: FRAME_BUDGET_MS,
+            frame_ms: 0.,
+            quality: 1.,
+            time: 0,
+            air_density: 1.,
+            wind: Wind::new(seed),
+            smoke: Smoke::new(seed),
+            smoky: true,
+            audio: AudioQueue::new(),
+            background: Background::default(),
+            trail_half_life: TRAIL_HALF_LIFE,
+            last_draw: 0,
+            seed,
+            rng: Rng::new(seed),
+            draw_order: Vec::new(),
+            batch: Vec::new(),
+        };
+        world.bodies.set_air(world.air_density, STEP as f64 / 1_000_000.);
+        world.set_viewport(width, height);
+        world.renderer.resize(width, height, 1.);
+        world
+    }
+
+    /// Fits the view box into a `width` by `height` pixel screen
+    fn set_viewport(&mut self, width: f64, height: f64) {
+        let (width, height) = (width.max(1.), height.max(1.)); // minimized windows and such
+        self.scale = (width / VIEW_WIDTH).min(height / VIEW_HEIGHT);
+        self.width = width / self.scale;
+        self.height = height / self.scale;
+    }
+
+    /// Pixels of the last drawn frame, if the renderer draws into memory
+    pub fn frame(&self) -> Option<&[u8]> {
+        self.renderer.frame()
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
     }
 
+    /// Positions of every live entity (in world units), in no particular order
+    pub fn positions(&self) -> Vec<(f64, f64)> {
+        self.bodies.x.iter().copied().zip(self.bodies.y.iter().copied()).collect()
+    }
+
+    /// Every sound that happened since the last drain, oldest first
+    pub fn audio_events(&mut self) -> Vec<AudioEvent> {
+        self.audio.drain()
+    }
+}
+
+#[wasm_bindgen]
+impl World {
+    /// Creates a world with a random seed, which gets logged to the console so
+    /// the show can be replayed later with `World::with_seed`
+    pub fn new(width: f64, height: f64) -> Self {
+        let seed = (Math::random() * u32::MAX as f64) as u32;
+        console::log_1(&format!("fireworks seed: {}", seed).into());
+        Self::with_seed(width, height, seed)
+    }
+
+    pub fn with_seed(width: f64, height: f64, seed: u32) -> Self {
+        let renderer = CanvasRenderer::new(utils::get_context("world"));
+        Self::with_renderer(width, height, seed, Box::new(renderer))
+    }
+
+    /// A world that doesn't touch the DOM and draws nothing. Useful for tests
+    /// and for running the simulation outside a browser
+    pub fn headless(width: f64, height: f64, seed: u32) -> Self {
+        Self::with_renderer(width, height, seed, Box::new(NullRenderer))
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn smoke_count(&self) -> usize {
+        self.smoke.len()
+    }
+
+    pub fn pending_fuses(&self) -> usize {
+        self.fuses.len()
+    }
+
+    /// `audio_events`, flattened for JS: `AudioEvent::STRIDE` numbers per event,
+    /// laid out as in `AudioEvent::to_array`. Call it every frame, the queue stops
+    /// growing if nobody does
+    pub fn drain_audio_events(&mut self) -> Vec<f64> {
+        self.audio.drain().iter().flat_map(|e| e.to_array()).collect()
+    }
+
+    /// Name of shell `id`, as found in burst events
+    pub fn shell_name(&self, id: usize) -> Option<String> {
+        self.shells.get(id).map(|s| s.name.clone())
+    }
+
+    /// Size of the world, in world units. At least 1280 by 720, and what show scripts
+    /// are written in
+    pub fn world_width(&self) -> f64 {
+        self.width
+    }
+
+    pub fn world_height(&self) -> f64 {
+        self.height
+    }
+
+    /// Css pixels per world unit
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// The screen is now `width` by `height` css pixels. Everything already flying keeps
+    /// its place on the screen, more or less
     pub fn resize(&mut self, width: f64, height: f64) {
-        self.width = width;
-        self.height = height;
+        let (old_width, old_height) = (self.width, self.height);
+        self.set_viewport(width, height);
+        if (self.width, self.height) != (old_width, old_height) {
+            self.remap(old_width, old_height);
+        }
+        self.renderer.resize(width, height, self.pixel_ratio);
+    }
+
+    /// `window.devicePixelRatio`. The canvas gets that many pixels per css pixel, so
+    /// sparks are sharp on HiDPI screens
+    pub fn set_pixel_ratio(&mut self, ratio: f64) {
+        if !ratio.is_finite() || ratio <= 0. || ratio == self.pixel_ratio {
+            return;
+        }
+        self.pixel_ratio = ratio;
+        self.renderer.resize(self.width * self.scale, self.height * self.scale, ratio);
+    }
+
+    /// Moves everything from a world `old_width` by `old_height` units big into this one
+    fn remap(&mut self, old_width: f64, old_height: f64) {
+        let (sx, sy) = (self.width / old_width, self.height / old_height);
+        self.bodies.remap(sx, sy);
+        self.smoke.remap(sx, sy);
+
+        // stretching text looks wrong, so lerper targets just follow the center. They
+        // still come up from the bottom, though
+        let (dx, dy) = ((self.width - old_width) / 2., (self.height - old_height) / 2.);
+        for behaviour in self.behaviours.iter_mut() {
+            if let Behaviour::Lerper(info) | Behaviour::UserLerper(info) = behaviour {
+                info.tx += dx;
+                info.ty += dy;
+                info.ix *= sx;
+                info.iy += self.height - old_height;
+            }
+        }
+    }
+
+    /// Stops drawing on the canvas and rasterizes into a buffer in wasm memory
+    /// instead. The page then shows it with `putImageData`, see `frame_ptr`
+    pub fn use_pixel_renderer(&mut self) {
+        let mut raster = RasterRenderer::new(0, 0);
+        raster.resize(self.width * self.scale, self.height * self.scale, self.pixel_ratio);
+        self.renderer = Box::new(raster);
+    }
+
+    /// Where the last frame's RGBA pixels start in wasm memory (null if the renderer
+    /// doesn't draw into memory). The buffer moves when the world is resized, and the
+    /// memory itself can grow, so get a fresh view of it every frame
+    pub fn frame_ptr(&self) -> *const u8 {
+        self.renderer.frame().map_or(std::ptr::null(), |f| f.as_ptr())
+    }
+
+    /// Length of the frame buffer, in bytes
+    pub fn frame_len(&self) -> usize {
+        self.renderer.frame().map_or(0, |f| f.len())
+    }
+
+    pub fn frame_width(&self) -> usize {
+        self.renderer.frame_size().map_or(0, |(w, _)| w)
+    }
+
+    pub fn frame_height(&self) -> usize {
+        self.renderer.frame_size().map_or(0, |(_, h)| h)
     }
 
     fn push(
@@ -55,17 +334,11 @@ impl World {
         body: Body,
         behaviour: Behaviour,
         fuse: Option<u64>
-    ) -> u32 {
+    ) -> Entity {
         // insert components
-        self.bodies.push(body);
+        self.bodies.push(body, &behaviour);
         self.behaviours.push(behaviour);
-
-        // insert id -> index in the entity_index map
-        let index = self.bodies.len() - 1;
-        let id = self.next_entity;
-        self.next_entity = self.next_entity.wrapping_add(1);
-        self.entity_index.insert(id, index);
-        self.entity_id.push(id);
+        let id = self.entities.spawn();
 
         if let Some(t) = fuse {
             self.fuses.insert((t, id));
@@ -76,203 +349,285 @@ impl World {
 
     fn push_random(&mut self, time: u64) {
         use Behaviour::*;
-        let bhv = components::random_behaviour();
-        let fuse = match bhv {
-            Comet(_) => 0u64, // just emit a particle as soon as possible, then `handle_fuse_event` will get the timing right
-            _ => (1_000_000. * (1. + Math::random())).floor() as u64, // 1-2s fuse
-        };
-        self.push(
-            Body {
-                x: Math::random() * self.width,
-                y: self.height + 10.,
-                vx: (Math::random() * 2. - 1.) * self.width / 10.,
-                vy: -Math::random() * 440. - 440.,
-                m: 1.,
-                r: match bhv {
-                    Crosette => Math::random() * 1.5 + 1.,
-                    Comet(_) => Math::random() * 4. + 1.,
-                    _ => Math::random() * 0.7 + 1.,
+        let bhv = self.random_shell();
+        let x = self.rng.random() * self.width;
+        let tilt = (self.rng.random() * 2. - 1.) * self.width / 10.;
+        let r = self.shell_radius(&bhv);
+        let color = utils::random_color(&mut self.rng);
+
+        if let Comet(_) = bhv {
+            // comets are their own tail, they just get thrown up there (faster on taller
+            // screens, so they go up the same part of it).
+            // Emit a particle as soon as possible, then `handle_fuse_event` will get the timing right
+            let vy = (-self.rng.random() * 440. - 440.) * (self.height / VIEW_HEIGHT).sqrt();
+            let body = Body { x, y: self.height + 10., vx: tilt, vy, m: 1., r, color };
+            let id = self.push(body, bhv, Some(time));
+            if let Some(i) = self.entities.get(id) {
+                self.bodies.launched[i] = true;
+            }
+            self.audio.push(time, x, self.height + 10., Sound::Launch { size: r });
+            return;
+        }
+
+        // a slow start out of the mortar, then the motor does most of the work, for just
+        // long enough to burst somewhere between 30% and 80% of the way up
+        let vy = -self.rng.random() * 100. - 120.;
+        let apogee = self.height * (self.rng.random() * 0.5 + 0.3) + 10.;
+        let burn = components::burn_for_apogee(-vy, ROCKET_THRUST, apogee);
+        let body = Body { x, y: self.height + 10., vx: tilt * 0.3, vy, m: 1., r, color };
+        let id = self.push(body, bhv, None);
+        if let Some(i) = self.entities.get(id) {
+            self.bodies.ignite(i, ROCKET_THRUST, burn);
+            self.bodies.launched[i] = true;
+        }
+        self.apogee_fuses.push(id);
+        self.audio.push(time, x, self.height + 10., Sound::Launch { size: r });
+        self.audio.push(time, x, self.height + 10., Sound::Whistle { duration: burn });
+    }
+
+    /// Adds shell types from a JSON list of specs (see `shells.rs`). A spec with the
+    /// same name as an existing shell replaces it. Returns how many specs were loaded,
+    /// counting inline stages
+    pub fn load_shells(&mut self, src: &str) -> Result<usize, String> {
+        let specs = shells::parse(src)?;
+        let n = specs.len();
+
+        let mut registry = self.shells.clone();
+        for spec in specs {
+            if spec.name == "Comet" {
+                return Err("Comet is taken, sorry".to_string());
+            }
+            match registry.iter().position(|s| s.name == spec.name) {
+                Some(id) => registry[id] = Rc::new(spec),
+                None => registry.push(Rc::new(spec)),
+            }
+        }
+
+        shells::validate(&registry)?;
+        self.shells = registry;
+        Ok(n)
+    }
+
+    /// Schedules every cue of a JSON show script (see `show.rs`) on the fuse queue.
+    /// `time` is in milliseconds, like everywhere else. Returns how many cues were loaded
+    pub fn load_show(&mut self, time: f64, src: &str) -> Result<usize, String> {
+        let show = Show::from_json(src)?;
+
+        // check everything before pushing anything
+        let mut checked = Vec::with_capacity(show.cues.len());
+        for cue in show.cues.iter() {
+            let shell = self.shell_by_name(&cue.shell)
+                .ok_or_else(|| format!("unknown shell {:?}", cue.shell))?;
+            let color = match &cue.color {
+                Some(CueColor::Hue(h)) => Color::new(*h, 85., 75.),
+                Some(CueColor::Css(css)) => utils::parse_color(css)?,
+                None => utils::random_color(&mut self.rng),
+            };
+            checked.push((shell, color));
+        }
+
+        let start = (time * 1_000.).floor() as u64;
+        for (cue, (shell, color)) in show.cues.iter().zip(checked) {
+            let r = match cue.size {
+                Some(r) => r,
+                None => self.shell_radius(&shell),
+            };
+            let launch = Launch {
+                vx: cue.vx,
+                vy: cue.vy,
+                r,
+                thrust: cue.thrust,
+                burn: cue.burn,
+                fuse: match (&shell, &cue.fuse) {
+                    (Behaviour::Comet(_), _) => Some(0), // emits right away, like in `push_random`
+                    (_, CueFuse::Seconds(fuse)) => Some((fuse * 1_000_000.).floor() as u64),
+                    (_, CueFuse::Apogee(_)) => None,
                 },
-                color: JsValue::from_str(&utils::random_color().to_string()),
-            },
-            bhv,
-            Some(time + fuse),
-        );
+                shell,
+            };
+
+            self.push(
+                Body {
+                    x: cue.x,
+                    y: cue.y.unwrap_or(self.height + 10.),
+                    vx: 0.,
+                    vy: 0.,
+                    m: 0., // doesn't fall out of the mortar either
+                    r: 0., // invisible until launched
+                    color,
+                },
+                Behaviour::Mortar(Box::new(launch)),
+                Some(start.saturating_add((cue.time * 1_000_000.).floor() as u64)),
+            );
+        }
+
+        self.random_launches = show.random_launches;
+        Ok(show.cues.len())
+    }
+
+    pub fn set_random_launches(&mut self, enabled: bool) {
+        self.random_launches = enabled;
+    }
+
+    /// Bursts stop adding stars once this many bodies are alive, and random launches wait
+    pub fn set_max_particles(&mut self, n: usize) {
+        self.max_particles = n;
     }
 
-    fn remove_id(&mut self, id: u32) {
-        // Remove entity_index entry
-        let i = self.entity_index.remove(&id).unwrap_or(0);
+    /// How long `report_frame_time` is allowed to say a frame took, in milliseconds
+    pub fn set_frame_budget(&mut self, ms: f64) {
+        self.frame_budget = ms.max(0.);
+    }
 
-        if self.entity_id.len() >= 2 {
-            // Fix the last index `entity_index` entry (will be swapped with `i` after `swap_remove`)
-            let last_id = self.entity_id.last().unwrap();
-            self.entity_index.insert(*last_id, i);
+    /// How long the last frame took, in milliseconds. Over budget, quality goes down
+    /// (smaller bursts, fewer launches, shorter trails). Comfortably under, it slowly
+    /// comes back up
+    pub fn report_frame_time(&mut self, ms: f64) {
+        if !ms.is_finite() || ms < 0. {
+            return;
         }
+        self.frame_ms = if self.frame_ms == 0. { ms } else { self.frame_ms * 0.9 + ms * 0.1 };
 
-        self.bodies.swap_remove(i);
-        self.behaviours.swap_remove(i);
-        self.entity_id.swap_remove(i);
+        if self.frame_ms > self.frame_budget {
+            self.quality = (self.quality * 0.95).max(MIN_QUALITY);
+        } else if self.frame_ms < self.frame_budget * 0.75 {
+            self.quality = (self.quality + 0.01).min(1.);
+        }
     }
 
-    /// What should a particle do when it receives a fuse event?
-    /// Returns the new behavior of the particle, or None if it should be deleted
-    pub fn handle_fuse_event(&mut self, time: u64, id: u32) {
-        let i = self.entity_index.get(&id).copied().unwrap_or(0);
+    pub fn quality(&self) -> f64 {
+        self.quality
+    }
 
-        use Behaviour::*;
-        let old_behaviour = std::mem::replace(&mut self.behaviours[i], Particle);
-        let new_behaviour = match old_behaviour {
-            Particle | Glitter(_) | HueParticle(_, _) | MassiveParticle => None, // particle's fuse just kills it
+    /// Thicker air, more drag for everything. 1 is the default, 0 is a vacuum
+    pub fn set_air_density(&mut self, density: f64) {
+        self.air_density = density.max(0.);
+        self.bodies.set_air(self.air_density, STEP as f64 / 1_000_000.);
+    }
 
-            Chris => { // Chris explodes
-                let n = (Math::random() * 150. + 150.) as i32;
-                let force = Math::random() * 300. + 400.;
-                for _ in 0..n {
-                    let fuse = time + (1000000. * (Math::random() + 0.5)) as u64;
-                    self.push(
-                        self.bodies[i].plus_explosion(n, force),
-                        Behaviour::Particle,
-                        Some(fuse),
-                    );
-                }
-                None
-            }
+    /// Steady wind, in units/s (positive y blows down)
+    pub fn set_wind(&mut self, x: f64, y: f64) {
+        self.wind.x = x;
+        self.wind.y = y;
+    }
 
-            MultiColorChris => {
-                let n = (Math::random() * 150. + 150.) as i32;
-                let colors = [self.bodies[i].color.clone(), JsValue::from_str(&utils::random_color().to_string())];
-                let force = Math::random() * 300. + 400.;
-                for _ in 0..n {
-                    let fuse = time + (1000000. * (Math::random() + 0.5)) as u64;
-                    self.push(
-                        self.bodies[i]
-                            .with_color((*utils::choose_from(&colors)).clone())
-                            .plus_explosion(n, force),
-                        Behaviour::Particle,
-                        Some(fuse),
-                    );
-                }
-                None
-            }
+    /// Gusts of up to about `strength` units/s on top of the steady wind, lasting
+    /// around `period` seconds each. 0 turns them off
+    pub fn set_gusts(&mut self, strength: f64, period: f64) {
+        self.wind.gusts = strength;
+        self.wind.period = period;
+    }
 
-            Strobe => {
-                let n = (Math::random() * 150. + 150.) as i32;
-                for _ in 0..n {
-                    let fuse = time + (1000000. * (Math::random() + 0.5)) as u64;
-                    let phi = (Math::random() * 6. + 3.) * 2.0 * std::f64::consts::PI;
-                    let force = Math::random() * 300. + 400.;
-                    self.push(
-                        self.bodies[i].plus_explosion(n, force),
-                        Behaviour::Glitter(phi),
-                        Some(fuse),
-                    );
-                }
-                None
-            }
+    /// How much stronger the wind gets with height: 0 is the same everywhere, 1 is
+    /// twice as strong at the top of the screen
+    pub fn set_wind_altitude(&mut self, gain: f64) {
+        self.wind.altitude = gain;
+    }
 
-            Peony => {
-                let n = (Math::random() * 150. + 150.) as i32;
-                let hue = Math::random() * 360.;
-                let freq = Math::random() * 2.5 + 0.5;
-                let force = Math::random() * 300. + 400.;
-                for _ in 0..n {
-                    let fuse = time + (1000000. * (Math::random() + 0.5)) as u64;
-                    self.push(
-                        self.bodies[i].plus_explosion(n, force),
-                        Behaviour::HueParticle(hue, freq),
-                        Some(fuse),
-                    );
-                }
-                None
-            }
+    /// Whether bursts and rising shells leave smoke behind. Turning it off lets the
+    /// smoke that's already there clear up on its own
+    pub fn set_smoke(&mut self, enabled: bool) {
+        self.smoky = enabled;
+    }
 
-            Willow => {
-                let n = (Math::random() * 150. + 150.) as i32;
-                let force = Math::random() * 300. + 400.;
-                for _ in 0..n {
-                    let fuse = time + (1000000. * (Math::random() + 0.5)) as u64;
-                    self.push(
-                        self.bodies[i].plus_willow_explosion(force),
-                        Behaviour::MassiveParticle,
-                        Some(fuse),
-                    );
-                }
-                None
-            }
+    /// How long the trails stay, as the time it takes one to fade to half its brightness.
+    /// 0 turns trails off and clears the whole canvas every frame
+    pub fn set_trail_half_life(&mut self, seconds: f64) {
+        self.trail_half_life = seconds.max(0.);
+    }
 
-            Crosette => {
-                let n = (Math::random() * 20. + 3.) as i32;
-                let force = Math::random() * 150. + 250.;
-                for _ in 0..n {
-                    let fuse = time + (1000000. * (Math::random() * 0.5 + 0.5)) as u64;
-                    self.push(
-                        self.bodies[i].plus_explosion(n, force),
-                        Behaviour::CrosetteBranch,
-                        Some(fuse),
-                    );
-                }
-                None
-            }
+    /// A solid `hsl(h,s%,l%)` background
+    pub fn set_background_color(&mut self, css: &str) -> Result<(), String> {
+        self.set_background(Background::Solid(utils::parse_color(css)?));
+        Ok(())
+    }
 
-            CrosetteBranch => {
-                let n = (Math::random() * 10. + 3.) as i32;
-                let force = Math::random() * 150. + 200.;
-                for _ in 0..n {
-                    let fuse = time + (1000000. * (Math::random() + 0.5)) as u64;
-                    self.push(
-                        self.bodies[i].plus_directed_explosion(n, force),
-                        Behaviour::Particle,
-                        Some(fuse),
-                    );
-                }
-                None
-            }
+    /// A vertical gradient from `top` to `bottom`, both `hsl(h,s%,l%)`
+    pub fn set_background_gradient(&mut self, top: &str, bottom: &str) -> Result<(), String> {
+        let top = utils::parse_color(top)?;
+        let bottom = utils::parse_color(bottom)?;
+        self.set_background(Background::Gradient { top, bottom });
+        Ok(())
+    }
 
-            Pistil => {
-                let n = (Math::random() * 150. + 150.) as i32; // outer explosion
-                let m = (Math::random() * 150. + 150.) as i32; // inner explosion
+    /// Nothing behind the fireworks, so whatever is under the canvas shows through
+    pub fn set_background_transparent(&mut self) {
+        self.set_background(Background::Transparent);
+    }
 
-                let force = Math::random() * 300. + 500.;
-                let mut body = self.bodies[i].clone();
-                body.r = self.bodies[i].r * 0.5;
-                for _ in 0..n {
-                    let fuse = time + (1000000. * (Math::random() + 0.5)) as u64;
-                    self.push(
-                        body.plus_explosion(n, force),
-                        Behaviour::Particle,
-                        Some(fuse),
-                    );
-                }
+    /// How many more bodies fit under `max_particles`
+    fn room(&self) -> usize {
+        self.max_particles.saturating_sub(self.bodies.len())
+    }
+
+    fn shell_by_name(&mut self, name: &str) -> Option<Behaviour> {
+        if name == "Comet" {
+            return Some(Behaviour::Comet((self.rng.random() * 45. + 20.).floor() as i32));
+        }
+        self.shells.iter().position(|s| s.name == name).map(Behaviour::Shell)
+    }
+
+    /// Any shell that's allowed to be launched randomly, or a comet
+    fn random_shell(&mut self) -> Behaviour {
+        let candidates: Vec<ShellId> = (0..self.shells.len()).filter(|&id| self.shells[id].random).collect();
+        let k = (self.rng.random() * (candidates.len() + 1) as f64).floor() as usize;
+        match candidates.get(k) {
+            Some(&id) => Behaviour::Shell(id),
+            None => Behaviour::Comet((self.rng.random() * 45. + 20.).floor() as i32),
+        }
+    }
+
+    /// Radius of a shell on its way up
+    fn shell_radius(&mut self, behaviour: &Behaviour) -> f64 {
+        match behaviour {
+            Behaviour::Shell(id) => self.shells[*id].size.sample(&mut self.rng),
+            Behaviour::Comet(_) => self.rng.random() * 4. + 1.,
+            _ => self.rng.random() * 0.7 + 1.,
+        }
+    }
+
+    fn remove(&mut self, id: Entity) {
+        if let Some(i) = self.entities.despawn(id) {
+            self.bodies.swap_remove(i);
+            self.behaviours.swap_remove(i);
+        }
+    }
+
+    /// What should a particle do when it receives a fuse event?
+    /// It either gets a new behaviour or gets deleted
+    fn handle_fuse_event(&mut self, time: u64, id: Entity) {
+        let i = match self.entities.get(id) {
+            Some(i) => i,
+            None => return, // stale fuse, that entity is already gone
+        };
+
+        use Behaviour::*;
+        let old_behaviour = std::mem::replace(&mut self.behaviours[i], Particle);
+        let new_behaviour = match old_behaviour {
+            Particle | Glitter(_) | HueParticle(_, _) | MassiveParticle => None, // particle's fuse just kills it
+
+            Shell(shell) => {
+                self.burst(time, i, shell);
+                None
+            }
 
-                let force = Math::random() * 150. + 150.;
-                body.r = self.bodies[i].r + Math::random() * 0.5;
-                body.color = JsValue::from_str(&utils::random_color().to_string());
-                for _ in 0..m {
-                    let fuse = time + (1000000. * (Math::random() + 0.5)) as u64;
+            Comet(rem) => {
+                let fuse = time + (500_000. * self.rng.random() + 500_000.).floor() as u64; // .5-1s fuse
+                let force = self.rng.random() * 150. + 100.;
+                let mut body = self.bodies.get(i);
+                body.r *= 0.5;
+                let child = body.plus_explosion(&mut self.rng, 1, force);
+                if self.room() > 0 {
                     self.push(
-                        body.plus_explosion(n, force),
+                        child,
                         Behaviour::Particle,
                         Some(fuse),
                     );
                 }
-                None
-            }
-
-            Comet(rem) => {
-                let fuse = time + (500_000. * Math::random() + 500_000.).floor() as u64; // .5-1s fuse
-                let force = Math::random() * 150. + 100.;
-                let mut body = self.bodies[i].clone();
-                body.r = self.bodies[i].r * 0.5;
-                self.push(
-                    body.plus_explosion(1, force),
-                    Behaviour::Particle,
-                    Some(fuse),
-                );
 
                 // kills the commet iff there are no more particles to emit
                 if rem-1 > 0 {
-                    let fuse = time + (20_000. * Math::random() + 20_000.).floor() as u64; // 30-130ms fuse
+                    let fuse = time + (20_000. * self.rng.random() + 20_000.).floor() as u64; // 30-130ms fuse
                     self.fuses.insert((fuse, id));
                     Some(Comet(rem-1))
                 } else {
@@ -281,30 +636,171 @@ impl World {
             }
 
             UserLerper(_) => {
-                // immediately fuses
+                self.audio.push(time, self.bodies.x[i], self.bodies.y[i], Sound::Arrival { count: 1 });
+                // immediately fuses, and bursts like it was launched
                 self.fuses.insert((0, id));
-                Some(components::random_behaviour())
+                self.bodies.launched[i] = true;
+                Some(self.random_shell())
+            }
+
+            Mortar(launch) => {
+                // lift off! the shell's own fuse starts now
+                self.bodies.vx[i] = launch.vx;
+                self.bodies.vy[i] = launch.vy;
+                self.bodies.m[i] = 1.;
+                self.bodies.r[i] = launch.r;
+                self.bodies.ignite(i, launch.thrust, launch.burn);
+                self.bodies.launched[i] = true;
+                let (x, y) = (self.bodies.x[i], self.bodies.y[i]);
+                self.audio.push(time, x, y, Sound::Launch { size: launch.r });
+                if launch.burn > 0. {
+                    self.audio.push(time, x, y, Sound::Whistle { duration: launch.burn });
+                }
+                match launch.fuse {
+                    Some(fuse) => { self.fuses.insert((time.saturating_add(fuse), id)); }
+                    None => self.apogee_fuses.push(id),
+                }
+                Some(launch.shell)
             }
 
             Lerper(_) => {
+                self.audio.push(time, self.bodies.x[i], self.bodies.y[i], Sound::Arrival { count: 1 });
                 // transforms into one exploded particle
-                let force = Math::random() * 300. + 400.;
-                let fuse = time + (1000000. * (Math::random() + 0.5)) as u64;
-                self.bodies[i] = self.bodies[i].plus_explosion(1, force);
-                self.bodies[i].r = 0.9;
+                let force = self.rng.random() * 300. + 400.;
+                let fuse = time + (1000000. * (self.rng.random() + 0.5)) as u64;
+                let mut body = self.bodies.get(i).plus_explosion(&mut self.rng, 1, force);
+                body.r = 0.9;
+                self.bodies.set(i, body);
                 self.fuses.insert((fuse, id));
                 Some(Particle)
             }
         };
 
         match new_behaviour {
-            Some(b) => { self.behaviours[i] = b; }
-            None => { self.remove_id(id); }
+            Some(b) => {
+                self.bodies.set_behaviour(i, &b);
+                self.behaviours[i] = b;
+            }
+            None => { self.remove(id); }
+        }
+    }
+
+    /// Bursts the body at index `i` into stars, as described by shell `shell`
+    fn burst(&mut self, time: u64, i: usize, shell: ShellId) {
+        let spec = self.shells[shell].clone();
+        let parent = self.bodies.get(i);
+        let mut total_stars = 0;
+        let mut energy = 0.;
+
+        // only for the shell that went up, stars bursting again (crosettes and such) would
+        // fill up all the smoke by themselves
+        if self.smoky && self.bodies.launched[i] {
+            // light up the smoke that's already around, then add some more
+            self.smoke.light(parent.x, parent.y, 250., parent.color.h as f64);
+            let puffs = (4. * self.quality).ceil() as usize;
+            self.smoke.burst(parent.x, parent.y, parent.vx, parent.vy, puffs);
+        }
+
+        for layer in spec.layers.iter() {
+            let n = layer.count.sample(&mut self.rng) as i32;
+            // fewer stars when we're struggling, but each one still gets the mass of a full
+            // burst's star, so they fly the same
+            let stars = ((n as f64 * self.quality).round() as usize).min(self.room());
+            let mut force = layer.force.sample(&mut self.rng);
+
+            let mut body = parent.clone();
+            body.r = parent.r * layer.radius_scale + layer.radius_add.sample(&mut self.rng);
+            let other_color = utils::random_color(&mut self.rng);
+            if let StarColor::Random = layer.color {
+                body.color = other_color;
+            }
+
+            // star parameters that are the same for the whole layer
+            let star = match &layer.star {
+                Star::Particle | Star::Glitter { .. } => Behaviour::Particle,
+                Star::Hue { freq } => Behaviour::HueParticle(self.rng.random() * 360., freq.sample(&mut self.rng)),
+                Star::Massive => Behaviour::MassiveParticle,
+                Star::Shell { name } => self.shell_by_name(name).unwrap_or(Behaviour::Particle),
+                Star::Stage { .. } => unreachable!("stages are flattened into shells when loaded"),
+            };
+
+            let mut fuses = 0.;
+            for _ in 0..stars {
+                let fuse = time.saturating_add((layer.fuse.sample(&mut self.rng) * 1_000_000.) as u64);
+                fuses += (fuse - time) as f64 / 1_000_000.;
+                if layer.force_per_star {
+                    force = layer.force.sample(&mut self.rng);
+                }
+
+                let behaviour = match &layer.star {
+                    Star::Glitter { freq } => Behaviour::Glitter(freq.sample(&mut self.rng) * 2.0 * std::f64::consts::PI),
+                    _ => star.clone(),
+                };
+
+                let mut child = match layer.explosion {
+                    Explosion::Sphere => body.plus_explosion(&mut self.rng, n, force),
+                    Explosion::Directed => body.plus_directed_explosion(&mut self.rng, n, force),
+                    Explosion::Willow => body.plus_willow_explosion(&mut self.rng, force),
+                };
+                if let StarColor::Mix = layer.color {
+                    if self.rng.random() < 0.5 {
+                        child.color = other_color;
+                    }
+                }
+
+                let (dvx, dvy) = (child.vx - parent.vx, child.vy - parent.vy);
+                energy += 0.5 * child.m * (dvx * dvx + dvy * dvy);
+                self.push(child, behaviour, Some(fuse));
+            }
+
+            total_stars += stars;
+            if let (Star::Glitter { .. }, true) = (&layer.star, stars > 0) {
+                // they crackle for about as long as they live
+                let duration = fuses / stars as f64;
+                self.audio.push(time, parent.x, parent.y, Sound::Crackle { stars, duration });
+            }
+        }
+
+        let sound = Sound::Burst { shell, size: parent.r, stars: total_stars, energy };
+        self.audio.push(time, parent.x, parent.y, sound);
+    }
+
+    /// Bursts the shells that stopped climbing (and are done burning)
+    fn update_apogee_fuses(&mut self, time: u64) {
+        let mut k = 0;
+        while k < self.apogee_fuses.len() {
+            let id = self.apogee_fuses[k];
+            let fire = match self.entities.get(id) {
+                Some(i) => self.bodies.vy[i] >= 0. && self.bodies.burn[i] <= 0.,
+                None => { self.apogee_fuses.swap_remove(k); continue; } // already gone
+            };
+            if fire {
+                self.apogee_fuses.swap_remove(k);
+                self.handle_fuse_event(time, id);
+            } else {
+                k += 1;
+            }
         }
     }
 
+    /// A short lived spark shooting out the back of the rocket at index `i`
+    fn push_tail_spark(&mut self, time: u64, i: usize) {
+        let (vx, vy) = (self.bodies.vx[i], self.bodies.vy[i]);
+        let spark = Body {
+            x: self.bodies.x[i],
+            y: self.bodies.y[i],
+            vx: -vx * 0.15 + (self.rng.random() - 0.5) * 60.,
+            vy: -vy * 0.15 + (self.rng.random() - 0.5) * 60.,
+            m: 0.3,
+            r: self.rng.random() * 0.4 + 0.6,
+            color: Color::new(self.rng.random() * 20. + 25., 100., 65.), // motor orange
+        };
+        let fuse = time + (self.rng.random() * 250_000. + 150_000.) as u64;
+        self.push(spark, Behaviour::Particle, Some(fuse));
+    }
+
     fn update_fuses(&mut self, time: u64) {
-        let mut fused = self.fuses.split_off(&(time+1, 0));
+        let mut fused = self.fuses.split_off(&(time+1, Entity::MIN));
         std::mem::swap(&mut fused, &mut self.fuses);
 
         if fused.is_empty() {
@@ -317,34 +813,121 @@ impl World {
         }
     }
 
-    fn update(&mut self, time: u64, dt: f64) {
-        if self.countdown == 0 && Math::random() < 0.06 {
+    /// Advances the simulation by exactly one `STEP`
+    fn update(&mut self, time: u64) {
+        let dt = STEP as f64 / 1_000_000.;
+
+        if self.random_launches && self.countdown == 0
+            && self.rng.random() < LAUNCHES_PER_SECOND * self.quality * dt
+            && self.room() > 0
+        {
             self.push_random(time);
         }
 
         self.update_fuses(time);
 
-        for (body, behaviour) in self.bodies.iter_mut()
-                                    .zip(self.behaviours.iter()) {
-            body.update(behaviour, dt, time);
+        self.bodies.save_positions();
+        let air = self.wind.air(time as f64 / 1_000_000., self.air_density, self.height);
+        self.bodies.integrate(dt, &air);
+        self.bodies.animate(&self.behaviours, time);
+
+        self.update_apogee_fuses(time);
+
+        for i in 0..self.bodies.len() {
+            let rising = self.bodies.launched[i]
+                && matches!(self.behaviours[i], Behaviour::Shell(_) | Behaviour::Comet(_))
+                && self.bodies.vy[i] < 0.;
+            if !rising {
+                continue;
+            }
+            if self.smoky {
+                self.smoke.trail(self.bodies.x[i], self.bodies.y[i], SHELL_SMOKE_PER_SECOND * self.quality, dt);
+            }
+            if self.bodies.burn[i] > 0. && self.room() > 0 && self.rng.random() < TAIL_SPARKS_PER_SECOND * self.quality * dt {
+                self.push_tail_spark(time, i);
+            }
         }
+        self.smoke.update(dt, &air);
     }
 
-    fn draw(&self) {
-        self.ctx.set_fill_style(&JsValue::from_str("rgb(0, 0, 0, 0.2)"));
-        self.ctx.fill_rect(0., 0., self.width, self.height);
+    /// `time` is the host's clock, in microsseconds. `alpha` is how far we are between
+    /// the last step and the next one, in [0, 1)
+    fn draw(&mut self, time: u64, alpha: f64) {
+        // trails lose half of what's left every half-life, however often we draw.
+        // Lower quality, shorter trails
+        let dt = time.saturating_sub(self.last_draw) as f64 / 1_000_000.;
+        self.last_draw = time;
+        let half_life = self.trail_half_life * self.quality;
+        let fade = if half_life > 0. { 1. - 0.5f64.powf(dt / half_life) } else { 1. };
+        let scale = self.scale;
+        self.renderer.fade(self.width * scale, self.height * scale, fade, self.background);
+
+        // smoke goes behind everything else. It gets drawn again every frame while only
+        // `fade` of it is painted over, so it would pile up without the `* fade`
+        let smoke = &self.smoke;
+        for i in 0..smoke.len() {
+            let (x, y, r) = (smoke.x[i], smoke.y[i], smoke.r[i]);
+            if x + r < 0. || x - r > self.width || y + r < 0. || y - r > self.height {
+                continue;
+            }
+            let (color, alpha) = smoke.look(i);
+            self.renderer.puff(x * scale, y * scale, r * scale, color, alpha * fade);
+        }
+
+        // group by color, so each color is one batch. Invisible bodies (mortars,
+        // glitter between blinks) don't even get sorted
+        let b = &self.bodies;
+        self.draw_order.clear();
+        self.draw_order.extend(
+            (0..b.len())
+                .filter(|&i| b.r[i] > 0.)
+                .map(|i| (b.color[i].key(), i as u32))
+        );
+        self.draw_order.sort_unstable();
+
+        let mut start = 0;
+        while start < self.draw_order.len() {
+            let key = self.draw_order[start].0;
+            let color = b.color[self.draw_order[start].1 as usize];
+
+            self.batch.clear();
+            let mut end = start;
+            while end < self.draw_order.len() && self.draw_order[end].0 == key {
+                let i = self.draw_order[end].1 as usize;
+                let x = b.px[i] + (b.x[i] - b.px[i]) * alpha;
+                let y = b.py[i] + (b.y[i] - b.py[i]) * alpha;
+                let r = b.r[i];
+                let offscreen = x + r < 0. || x - r > self.width || y + r < 0. || y - r > self.height;
+                if !offscreen {
+                    self.batch.push((x * scale, y * scale, r * scale));
+                }
+                end += 1;
+            }
 
-        for body in self.bodies.iter() {
-            body.draw(&self.ctx);
+            if !self.batch.is_empty() {
+                self.renderer.circles(color, &self.batch);
+            }
+            start = end;
         }
     }
     
     // "loop" is a keyword
-    // beware: time is in milliseconds, dt is in seconds
-    pub fn loopity_loop(&mut self, time: f64, dt: f64) {
+    /// Runs as many fixed steps as needed to catch up with `time` (in milliseconds!),
+    /// then draws
+    pub fn loopity_loop(&mut self, time: f64) {
         let time = (time * 1000.).floor() as u64; // and now time is in microsseconds
-        self.update(time, dt);
-        self.draw();
+
+        if time > self.time + MAX_CATCHUP {
+            self.time = time - MAX_CATCHUP;
+        }
+
+        while self.time + STEP <= time {
+            self.time += STEP;
+            self.update(self.time);
+        }
+
+        let alpha = time.saturating_sub(self.time) as f64 / STEP as f64;
+        self.draw(time, alpha);
     }
 
     pub fn update_countdown(&mut self, time: f64, seconds: f64) {
@@ -391,16 +974,22 @@ impl World {
             .build();
 
         for p in points {
-            self.push_lerper(time, p.x, p.y, false);
+            self.spawn_lerper(time, p.x, p.y, false);
         }
     }
 
+    /// A lerper flying to (`tx`, `ty`), in pixels (like where the user clicked)
     pub fn push_lerper(&mut self, time: f64, tx: f64, ty: f64, user: bool) {
+        self.spawn_lerper(time, tx / self.scale, ty / self.scale, user);
+    }
+
+    /// `push_lerper`, but in world units
+    fn spawn_lerper(&mut self, time: f64, tx: f64, ty: f64, user: bool) {
         let (fuse, ix, iy, r);
         if user {
-            fuse = Math::random() * 0.5 + 0.75;
-            ix = Math::random() * self.width;
-            r = Math::random() * 0.7 + 1.;
+            fuse = self.rng.random() * 0.5 + 0.75;
+            ix = self.rng.random() * self.width;
+            r = self.rng.random() * 0.7 + 1.;
         } else {
             fuse = 1.;
             ix = self.width / 2.;
@@ -412,6 +1001,7 @@ impl World {
         let it = (time * 1_000.).floor() as u64;
         let tt = it + (fuse * 1_000_000.).floor() as u64;
         let info = LerperInfo::boxed(ix, iy, it, tx, ty, tt);
+        let color = utils::random_color(&mut self.rng);
 
         self.push(
             Body {
@@ -421,7 +1011,7 @@ impl World {
                 vy: 0.,
                 m: 1.,
                 r,
-                color: JsValue::from_str(&utils::random_color().to_string()),
+                color,
             },
             if user { Behaviour::UserLerper(info) } else { Behaviour::Lerper(info) },
             Some(tt),
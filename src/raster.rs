@@ -0,0 +1,135 @@
+use crate::render::{Background, Renderer};
+use crate::utils::Color;
+
+/// Software backend that draws into an RGBA buffer (row-major, 4 bytes per pixel,
+/// straight alpha, which is the layout `ImageData` wants, so the browser can take the
+/// buffer as is). Mimics what the canvas does closely enough that frames can be
+/// compared pixel by pixel
+pub struct RasterRenderer {
+    width: usize, // of the buffer, in device pixels
+    height: usize,
+    ratio: f64, // device pixels per css pixel, which is what we get drawing calls in
+    pixels: Vec<u8>,
+}
+
+impl RasterRenderer {
+    /// Starts fully transparent, like a fresh canvas
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            ratio: 1.,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    /// Source-over blend of an opaque `rgb` with `coverage` (or alpha) in [0, 1]
+    #[inline]
+    fn blend(&mut self, i: usize, rgb: [u8; 3], coverage: f32) {
+        let px = &mut self.pixels[i..i + 4];
+        let below = px[3] as f32 / 255. * (1. - coverage); // what's left of the old pixel
+        let a = coverage + below;
+        for c in 0..3 {
+            px[c] = ((rgb[c] as f32 * coverage + px[c] as f32 * below) / a).round() as u8;
+        }
+        px[3] = (255. * a).round() as u8;
+    }
+
+    /// Anti-aliased circle, `alpha` in [0, 1]
+    fn fill_circle(&mut self, x: f64, y: f64, r: f64, color: Color, alpha: f64) {
+        if r <= 0. {
+            return;
+        }
+        let rgb = color.to_rgb();
+
+        // bounding box, clipped to the buffer
+        let x0 = (x - r - 1.).floor().max(0.) as usize;
+        let y0 = (y - r - 1.).floor().max(0.) as usize;
+        let x1 = ((x + r + 1.).ceil().max(0.) as usize).min(self.width);
+        let y1 = ((y + r + 1.).ceil().max(0.) as usize).min(self.height);
+
+        for py in y0..y1 {
+            for px in x0..x1 {
+                // distance from the pixel center, with a one pixel wide soft edge
+                let dx = px as f64 + 0.5 - x;
+                let dy = py as f64 + 0.5 - y;
+                let d = (dx * dx + dy * dy).sqrt();
+                let coverage = (r - d + 0.5).clamp(0., 1.).min(r * r * std::f64::consts::PI) * alpha;
+                if coverage > 0. {
+                    self.blend((py * self.width + px) * 4, rgb, coverage as f32);
+                }
+            }
+        }
+    }
+}
+
+impl Renderer for RasterRenderer {
+    fn fade(&mut self, width: f64, height: f64, alpha: f64, background: Background) {
+        let (width, height) = (width * self.ratio, height * self.ratio);
+        let w = (width.max(0.).ceil() as usize).min(self.width);
+        let h = (height.max(0.).ceil() as usize).min(self.height);
+        let alpha = alpha.clamp(0., 1.) as f32;
+        if alpha == 0. {
+            return;
+        }
+
+        for y in 0..h {
+            let row = y * self.width * 4;
+            match background.rgb_at((y as f64 + 0.5) / height) {
+                Some(rgb) => {
+                    for i in (row..row + w * 4).step_by(4) {
+                        self.blend(i, rgb, alpha);
+                    }
+                }
+                None => {
+                    // destination-out, the colors stay and only the alpha goes
+                    for px in self.pixels[row..row + w * 4].chunks_exact_mut(4) {
+                        px[3] = (px[3] as f32 * (1. - alpha)).round() as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    fn circle(&mut self, x: f64, y: f64, r: f64, color: Color) {
+        let k = self.ratio;
+        self.fill_circle(x * k, y * k, r * k, color, 1.);
+    }
+
+    fn puff(&mut self, x: f64, y: f64, r: f64, color: Color, alpha: f64) {
+        let k = self.ratio;
+        self.fill_circle(x * k, y * k, r * k, color, alpha.clamp(0., 1.));
+    }
+
+    /// Starts over with a transparent buffer of the new size, like a canvas does
+    fn resize(&mut self, width: f64, height: f64, pixel_ratio: f64) {
+        let (w, h) = (width * pixel_ratio, height * pixel_ratio);
+        *self = Self::new(w.max(0.).ceil() as usize, h.max(0.).ceil() as usize);
+        self.ratio = pixel_ratio;
+    }
+
+    fn frame(&self) -> Option<&[u8]> {
+        Some(&self.pixels)
+    }
+
+    fn frame_size(&self) -> Option<(usize, usize)> {
+        Some((self.width, self.height))
+    }
+}
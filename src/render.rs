@@ -0,0 +1,224 @@
+use crate::utils::Color;
+use web_sys::CanvasRenderingContext2d;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// What's behind the fireworks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    Solid(Color),
+    Gradient { top: Color, bottom: Color }, // vertical, like a sky
+    Transparent, // for hosts that put the canvas over other content
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(Color::new(0., 0., 0.))
+    }
+}
+
+impl Background {
+    /// Color `t` of the way down the screen, or `None` if there's nothing to paint
+    pub fn rgb_at(&self, t: f64) -> Option<[u8; 3]> {
+        match self {
+            Background::Solid(c) => Some(c.to_rgb()),
+            Background::Gradient { top, bottom } => {
+                let (top, bottom) = (top.to_rgb(), bottom.to_rgb());
+                let t = t.clamp(0., 1.);
+                Some([0, 1, 2].map(|c| (top[c] as f64 * (1. - t) + bottom[c] as f64 * t).round() as u8))
+            }
+            Background::Transparent => None,
+        }
+    }
+}
+
+/// Everything `World::draw` needs from a drawing backend
+pub trait Renderer {
+    /// Paints the background over everything with some `alpha`, which is how we fake
+    /// the motion trails. With a transparent background, it erases by `alpha` instead.
+    /// An `alpha` of 1 clears the whole thing
+    fn fade(&mut self, width: f64, height: f64, alpha: f64, background: Background);
+
+    /// Filled circle
+    fn circle(&mut self, x: f64, y: f64, r: f64, color: Color);
+
+    /// See-through filled circle, for smoke
+    fn puff(&mut self, x: f64, y: f64, r: f64, color: Color, alpha: f64);
+
+    /// Many filled circles of the same color, as `(x, y, r)`
+    fn circles(&mut self, color: Color, circles: &[(f64, f64, f64)]) {
+        for &(x, y, r) in circles {
+            self.circle(x, y, r, color);
+        }
+    }
+
+    /// The screen is now `width` by `height` css pixels, with `pixel_ratio` device pixels
+    /// each. Drawing calls stay in css pixels, backends that own their pixels should make
+    /// them sharp
+    fn resize(&mut self, _width: f64, _height: f64, _pixel_ratio: f64) {}
+
+    /// The RGBA pixels of the last frame, for backends that draw into memory
+    fn frame(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Width and height of `frame`, in pixels
+    fn frame_size(&self) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+/// Past this many cached styles we just start over. Hue particles cycle through
+/// every hue, but that's still only 360 of them per saturation/lightness
+const MAX_CACHED_STYLES: usize = 4096;
+
+/// The browser backend
+pub struct CanvasRenderer {
+    ctx: CanvasRenderingContext2d,
+    styles: HashMap<u32, String>, // Color::key -> css
+    current: Option<Color>, // fill style the context has right now
+}
+
+impl CanvasRenderer {
+    pub fn new(ctx: CanvasRenderingContext2d) -> Self {
+        Self {
+            ctx,
+            styles: HashMap::new(),
+            current: None,
+        }
+    }
+
+    /// Only talks to the context if the color actually changed, and only formats
+    /// the css string the first time we see a color
+    fn set_fill(&mut self, color: Color) {
+        if self.current == Some(color) {
+            return;
+        }
+        if self.styles.len() >= MAX_CACHED_STYLES {
+            self.styles.clear();
+        }
+        let style = self.styles.entry(color.key()).or_insert_with(|| color.to_string());
+        self.ctx.set_fill_style_str(style);
+        self.current = Some(color);
+    }
+}
+
+impl Renderer for CanvasRenderer {
+    /// Sizes the canvas' backing store to the device pixels, then scales everything
+    /// back down so we can keep drawing in css pixels. The canvas element itself gets
+    /// stretched over the page by css
+    fn resize(&mut self, width: f64, height: f64, pixel_ratio: f64) {
+        if let Some(canvas) = self.ctx.canvas() {
+            canvas.set_width((width * pixel_ratio).ceil() as u32);
+            canvas.set_height((height * pixel_ratio).ceil() as u32);
+        }
+        // resizing resets the whole context, fill style included
+        self.ctx.set_transform(pixel_ratio, 0., 0., pixel_ratio, 0., 0.).unwrap();
+        self.current = None;
+    }
+
+    fn fade(&mut self, width: f64, height: f64, alpha: f64, background: Background) {
+        match background {
+            Background::Transparent if alpha >= 1. => {
+                self.ctx.clear_rect(0., 0., width, height);
+                return;
+            }
+            Background::Transparent => {
+                self.ctx.set_global_composite_operation("destination-out").unwrap();
+                self.set_fill(Color::new(0., 0., 0.));
+            }
+            Background::Solid(color) => self.set_fill(color),
+            Background::Gradient { top, bottom } => {
+                let gradient = self.ctx.create_linear_gradient(0., 0., 0., height);
+                gradient.add_color_stop(0., &top.to_string()).unwrap();
+                gradient.add_color_stop(1., &bottom.to_string()).unwrap();
+                self.ctx.set_fill_style_canvas_gradient(&gradient);
+                self.current = None;
+            }
+        }
+
+        self.ctx.set_global_alpha(alpha);
+        self.ctx.fill_rect(0., 0., width, height);
+        self.ctx.set_global_alpha(1.);
+        self.ctx.set_global_composite_operation("source-over").unwrap();
+    }
+
+    #[inline]
+    fn circle(&mut self, x: f64, y: f64, r: f64, color: Color) {
+        self.set_fill(color);
+        self.ctx.begin_path();
+        self.ctx.arc(x, y, r, 0.0, std::f64::consts::PI * 2.0).unwrap();
+        self.ctx.fill();
+    }
+
+    fn puff(&mut self, x: f64, y: f64, r: f64, color: Color, alpha: f64) {
+        self.ctx.set_global_alpha(alpha);
+        self.circle(x, y, r, color);
+        self.ctx.set_global_alpha(1.);
+    }
+
+    /// One path and one fill for the whole group, instead of 4 calls per circle
+    fn circles(&mut self, color: Color, circles: &[(f64, f64, f64)]) {
+        self.set_fill(color);
+        self.ctx.begin_path();
+        for &(x, y, r) in circles {
+            self.ctx.move_to(x + r, y); // or else the arcs get connected by lines
+            self.ctx.arc(x, y, r, 0.0, std::f64::consts::PI * 2.0).unwrap();
+        }
+        self.ctx.fill();
+    }
+}
+
+/// Draws nothing at all, for worlds that only need to be stepped
+pub struct NullRenderer;
+
+impl Renderer for NullRenderer {
+    fn fade(&mut self, _width: f64, _height: f64, _alpha: f64, _background: Background) {}
+    fn circle(&mut self, _x: f64, _y: f64, _r: f64, _color: Color) {}
+    fn puff(&mut self, _x: f64, _y: f64, _r: f64, _color: Color, _alpha: f64) {}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCall {
+    Fade { width: f64, height: f64, alpha: f64, background: Background },
+    Circle { x: f64, y: f64, r: f64, color: Color },
+    Circles { color: Color, circles: Vec<(f64, f64, f64)> },
+    Puff { x: f64, y: f64, r: f64, color: Color, alpha: f64 },
+}
+
+/// Backend that just remembers what it was asked to draw. Clones share the same
+/// recording, so keep one around to look at the calls after handing the other to a `World`
+#[derive(Clone, Default)]
+pub struct RecordingRenderer {
+    calls: Rc<RefCell<Vec<DrawCall>>>,
+}
+
+impl RecordingRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns everything drawn since the last call and clears the recording
+    pub fn take(&self) -> Vec<DrawCall> {
+        std::mem::take(&mut self.calls.borrow_mut())
+    }
+}
+
+impl Renderer for RecordingRenderer {
+    fn fade(&mut self, width: f64, height: f64, alpha: f64, background: Background) {
+        self.calls.borrow_mut().push(DrawCall::Fade { width, height, alpha, background });
+    }
+
+    fn circle(&mut self, x: f64, y: f64, r: f64, color: Color) {
+        self.calls.borrow_mut().push(DrawCall::Circle { x, y, r, color });
+    }
+
+    fn puff(&mut self, x: f64, y: f64, r: f64, color: Color, alpha: f64) {
+        self.calls.borrow_mut().push(DrawCall::Puff { x, y, r, color, alpha });
+    }
+
+    fn circles(&mut self, color: Color, circles: &[(f64, f64, f64)]) {
+        self.calls.borrow_mut().push(DrawCall::Circles { color, circles: circles.to_vec() });
+    }
+}
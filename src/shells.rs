@@ -0,0 +1,224 @@
+//! Shells as data. Every shell that bursts into stars is described by a `ShellSpec`,
+//! and `World::burst` is the one routine that reads them. The built-in ones live in
+//! `shells.json`, and more can be loaded at runtime with `World::load_shells`.
+//!
+//! Counts are numbers of stars, forces are in world units/s, fuses in seconds. Every
+//! `[lo, hi]` range is sampled uniformly.
+//!
+//! Stars can be shells themselves, to any depth: either another shell by name (`"Comet"`
+//! works too), or an inline `Stage` with its own layers. Inline stages get flattened into the registry
+//! with names like `"Crosette/0"` (parent name / layer index).
+
+use crate::rng::Rng;
+use serde::Deserialize;
+use std::rc::Rc;
+
+pub type ShellId = usize;
+
+/// How many times a star can burst into more stars. Mostly there to catch loops
+pub const MAX_DEPTH: usize = 8;
+
+/// Upper bound on how many stars a single shell can end up making, over all its stages
+pub const MAX_STARS: f64 = 20_000.;
+
+/// Most bodies a `"Comet"` star turns into, itself and its sparks (see `World::shell_by_name`)
+pub const COMET_STARS: f64 = 65.;
+
+/// Longest star fuse, in seconds
+pub const MAX_FUSE: f64 = 60.;
+
+/// Fastest a layer can throw its stars, in world units/s. Several screens per second
+pub const MAX_FORCE: f64 = 10_000.;
+
+const BUILTIN: &str = include_str!("shells.json");
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Range(pub f64, pub f64);
+
+impl Range {
+    #[inline]
+    pub fn sample(&self, rng: &mut Rng) -> f64 {
+        self.0 + rng.random() * (self.1 - self.0)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShellSpec {
+    pub name: String,
+
+    /// Radius of the shell on its way up
+    #[serde(default = "default_size")]
+    pub size: Range,
+
+    /// Can `push_random` pick this one? Shells that only exist as stars of other
+    /// shells should say no
+    #[serde(default = "yes")]
+    pub random: bool,
+
+    /// Everything that comes out when it bursts. Most shells have only one layer,
+    /// pistils have an inner and an outer one
+    pub layers: Vec<Layer>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Layer {
+    pub count: Range,
+    pub force: Range,
+
+    /// Sample the force for every star instead of once per burst. Makes it fuzzier
+    #[serde(default)]
+    pub force_per_star: bool,
+
+    #[serde(default = "default_fuse")]
+    pub fuse: Range,
+
+    #[serde(default)]
+    pub explosion: Explosion,
+
+    #[serde(default)]
+    pub star: Star,
+
+    #[serde(default)]
+    pub color: StarColor,
+
+    #[serde(default = "one")]
+    pub radius_scale: f64,
+
+    #[serde(default = "zero_range")]
+    pub radius_add: Range,
+}
+
+/// Which `Body::plus_*_explosion` throws the stars around
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum Explosion {
+    #[default]
+    Sphere,
+    Directed, // a cone following the shell's velocity
+    Willow, // droopy
+}
+
+/// What each star does after the burst
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "type")]
+pub enum Star {
+    #[default]
+    Particle,
+    Glitter { freq: Range }, // blinks `freq` times per second, sampled per star
+    Hue { freq: Range }, // cycles through hues, sampled per burst
+    Massive, // falls, barely any drag
+    Shell { name: String }, // bursts again, as another shell
+    Stage { layers: Vec<Layer> }, // bursts again, as an inline shell with these layers
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum StarColor {
+    #[default]
+    Inherit,
+    Random, // one new random color for the whole layer
+    Mix, // each star is either the shell's color or one new random color
+}
+
+fn default_size() -> Range { Range(1., 1.7) }
+fn default_fuse() -> Range { Range(0.5, 1.5) }
+fn zero_range() -> Range { Range(0., 0.) }
+fn one() -> f64 { 1. }
+fn yes() -> bool { true }
+
+/// Parses a list of specs. Inline stages come out as specs of their own, right
+/// after their parent
+pub fn parse(src: &str) -> Result<Vec<ShellSpec>, String> {
+    let parsed: Vec<ShellSpec> = serde_json::from_str(src).map_err(|e| e.to_string())?;
+    let mut specs = Vec::with_capacity(parsed.len());
+    for spec in parsed {
+        flatten(spec, &mut specs);
+    }
+
+    for spec in specs.iter() {
+        if spec.layers.is_empty() {
+            return Err(format!("{}: a shell needs at least one layer", spec.name));
+        }
+        let ranges = spec.layers.iter()
+            .flat_map(|l| [l.count, l.force, l.fuse, l.radius_add])
+            .chain([spec.size]);
+        for Range(lo, hi) in ranges {
+            if lo < 0. || lo > hi {
+                return Err(format!("{}: bad range [{}, {}]", spec.name, lo, hi));
+            }
+        }
+        for layer in spec.layers.iter() {
+            if layer.count.1 > MAX_STARS || layer.force.1 > MAX_FORCE || layer.fuse.1 > MAX_FUSE {
+                return Err(format!(
+                    "{}: at most {} stars, thrown at {} units/s, with {}s fuses",
+                    spec.name, MAX_STARS, MAX_FORCE, MAX_FUSE
+                ));
+            }
+        }
+    }
+    Ok(specs)
+}
+
+/// Turns every `Star::Stage` into a `Star::Shell` pointing to a new spec
+fn flatten(mut spec: ShellSpec, out: &mut Vec<ShellSpec>) {
+    let mut stages = Vec::new();
+    for (i, layer) in spec.layers.iter_mut().enumerate() {
+        if let Star::Stage { .. } = layer.star {
+            let name = format!("{}/{}", spec.name, i);
+            let star = std::mem::replace(&mut layer.star, Star::Shell { name: name.clone() });
+            if let Star::Stage { layers } = star {
+                stages.push(ShellSpec {
+                    name,
+                    size: default_size(),
+                    random: false,
+                    layers,
+                });
+            }
+        }
+    }
+
+    out.push(spec);
+    for stage in stages {
+        flatten(stage, out);
+    }
+}
+
+/// Checks that every star shell exists, that nothing loops or nests deeper than
+/// `MAX_DEPTH`, and that no shell makes more than `MAX_STARS` stars
+pub fn validate(registry: &[Rc<ShellSpec>]) -> Result<(), String> {
+    fn stars(registry: &[Rc<ShellSpec>], spec: &ShellSpec, depth: usize) -> Result<f64, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("{}: stages nest deeper than {} (is there a loop?)", spec.name, MAX_DEPTH));
+        }
+
+        let mut total = 0.;
+        for layer in spec.layers.iter() {
+            let per_star = match &layer.star {
+                Star::Shell { name } if name == "Comet" => COMET_STARS,
+                Star::Shell { name } => {
+                    let child = registry.iter().find(|s| &s.name == name)
+                        .ok_or_else(|| format!("{}: unknown star shell {:?}", spec.name, name))?;
+                    stars(registry, child, depth + 1)?
+                }
+                _ => 1.,
+            };
+            total += layer.count.1.floor() * per_star;
+        }
+        Ok(total)
+    }
+
+    for spec in registry.iter() {
+        let n = stars(registry, spec, 0)?;
+        if n > MAX_STARS {
+            return Err(format!("{}: could make up to {} stars, the limit is {}", spec.name, n, MAX_STARS));
+        }
+    }
+    Ok(())
+}
+
+pub fn builtin() -> Vec<Rc<ShellSpec>> {
+    let registry: Vec<_> = parse(BUILTIN).expect("built-in shells.json is broken")
+        .into_iter()
+        .map(Rc::new)
+        .collect();
+    validate(&registry).expect("built-in shells.json is broken");
+    registry
+}
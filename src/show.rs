@@ -0,0 +1,91 @@
+//! Show scripts: timed launches, so a show can be choreographed instead of random.
+//!
+//! ```json
+//! {
+//!     "cues": [
+//!         { "time": 0.5, "x": 300, "vx": 20, "vy": -700, "shell": "Peony", "color": 210, "fuse": 1.5 },
+//!         { "time": 1.0, "x": 900, "vy": -800, "shell": "Willow", "color": "hsl(40,85%,75%)", "fuse": 1.2 },
+//!         { "time": 2.0, "x": 600, "vy": -200, "thrust": 1300, "burn": 0.5, "shell": "Pistil", "fuse": "apogee" }
+//!     ]
+//! }
+//! ```
+//!
+//! Positions are in world units (the screen is at least 1280 of them wide and 720 tall,
+//! see `World::world_width`), velocities in units/s and times in seconds.
+//! `time` is counted from when the show is loaded, `fuse` from the launch, and none of
+//! them (or `burn`) can be longer than a day.
+//! Shells with a `burn` time get pushed along by their motor (`thrust`, in units/s²)
+//! for that long after leaving the mortar, and a fuse of `"apogee"` bursts them at the
+//! top of their climb.
+
+use serde::Deserialize;
+
+/// Longest `time`, `fuse` or `burn` a cue can have, in seconds. A day is plenty, and
+/// keeps the fuse queue far from overflowing
+pub const MAX_SECONDS: f64 = 86_400.;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Show {
+    pub cues: Vec<Cue>,
+
+    /// Keep launching random shells after the countdown, on top of the cues
+    #[serde(default)]
+    pub random_launches: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cue {
+    pub time: f64,
+    pub x: f64,
+    pub y: Option<f64>, // defaults to just below the bottom of the screen
+    #[serde(default)]
+    pub vx: f64,
+    pub vy: f64,
+    pub shell: String, // a shell name from `shells.rs` (like "Peony"), or "Comet"
+    pub color: Option<CueColor>, // a hue or a "hsl(h,s%,l%)" string, random if missing
+    pub size: Option<f64>, // radius, depends on the shell if missing
+    #[serde(default)]
+    pub thrust: f64,
+    #[serde(default)]
+    pub burn: f64,
+    pub fuse: CueFuse,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum CueFuse {
+    Seconds(f64),
+    Apogee(String), // has to be "apogee"
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CueColor {
+    Hue(f64),
+    Css(String),
+}
+
+impl Show {
+    pub fn from_json(src: &str) -> Result<Self, String> {
+        let show: Show = serde_json::from_str(src).map_err(|e| e.to_string())?;
+        for (i, cue) in show.cues.iter().enumerate() {
+            let fuse = match &cue.fuse {
+                CueFuse::Seconds(fuse) => *fuse,
+                CueFuse::Apogee(s) if s == "apogee" => 0.,
+                CueFuse::Apogee(s) => {
+                    return Err(format!("cue {}: fuse is either in seconds or \"apogee\", not {:?}", i, s));
+                }
+            };
+            if cue.time < 0. || fuse < 0. || cue.thrust < 0. || cue.burn < 0. {
+                return Err(format!("cue {}: time, fuse, thrust and burn can't be negative", i));
+            }
+            if cue.time > MAX_SECONDS || fuse > MAX_SECONDS || cue.burn > MAX_SECONDS {
+                return Err(format!("cue {}: time, fuse and burn can't be longer than a day", i));
+            }
+            if cue.size.is_some_and(|r| r < 0.) {
+                return Err(format!("cue {}: size can't be negative", i));
+            }
+        }
+        Ok(show)
+    }
+}
@@ -0,0 +1,201 @@
+//! Smoke: big, slow, see-through puffs left behind by bursts and rising shells.
+//! They grow, drift with the wind and fade out over a few seconds, and light up
+//! for a moment when something bursts next to them.
+//!
+//! There's never more than `MAX_PUFFS` of them, new ones just don't get made until
+//! old ones clear up.
+
+use crate::rng::Rng;
+use crate::utils::Color;
+use crate::wind::Air;
+
+pub const MAX_PUFFS: usize = 400;
+
+/// How fast puffs catch up with the wind, per second. Smoke goes wherever the air goes
+const DRAG: f64 = 1.5;
+
+/// Hot smoke rises a little, in units/s
+const BUOYANCY: f64 = -12.;
+
+/// Half-life of the glow from a nearby burst, in seconds
+const GLOW_HALF_LIFE: f64 = 0.25;
+
+/// A new puff
+pub struct Puff {
+    pub x: f64,
+    pub y: f64,
+    pub vx: f64,
+    pub vy: f64,
+    pub r: f64,
+    pub growth: f64, // radius gained per second
+    pub life: f64, // seconds until it's gone
+    pub opacity: f64, // alpha it's born with
+}
+
+/// Every puff, as a structure of arrays like `Bodies`
+pub struct Smoke {
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub vx: Vec<f64>,
+    pub vy: Vec<f64>,
+    pub r: Vec<f64>,
+    pub growth: Vec<f64>,
+    pub age: Vec<f64>, // seconds
+    pub life: Vec<f64>,
+    pub opacity: Vec<f64>,
+    pub glow: Vec<f64>, // 0 to 1, from the last burst that lit it up
+    pub hue: Vec<f64>, // of that burst
+    rng: Rng,
+}
+
+impl Smoke {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            x: Vec::new(),
+            y: Vec::new(),
+            vx: Vec::new(),
+            vy: Vec::new(),
+            r: Vec::new(),
+            growth: Vec::new(),
+            age: Vec::new(),
+            life: Vec::new(),
+            opacity: Vec::new(),
+            glow: Vec::new(),
+            hue: Vec::new(),
+            // not the world's rng, so smoke doesn't change the show
+            rng: Rng::new(seed ^ 0x534D_4F4B),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Does nothing if there's no room left
+    pub fn push(&mut self, puff: Puff) {
+        if self.len() >= MAX_PUFFS {
+            return;
+        }
+        self.x.push(puff.x);
+        self.y.push(puff.y);
+        self.vx.push(puff.vx);
+        self.vy.push(puff.vy);
+        self.r.push(puff.r);
+        self.growth.push(puff.growth);
+        self.age.push(0.);
+        self.life.push(puff.life);
+        self.opacity.push(puff.opacity);
+        self.glow.push(0.);
+        self.hue.push(0.);
+    }
+
+    fn swap_remove(&mut self, i: usize) {
+        self.x.swap_remove(i);
+        self.y.swap_remove(i);
+        self.vx.swap_remove(i);
+        self.vy.swap_remove(i);
+        self.r.swap_remove(i);
+        self.growth.swap_remove(i);
+        self.age.swap_remove(i);
+        self.life.swap_remove(i);
+        self.opacity.swap_remove(i);
+        self.glow.swap_remove(i);
+        self.hue.swap_remove(i);
+    }
+
+    /// A cloud of `n` puffs where a shell burst, moving a bit like the shell did
+    pub fn burst(&mut self, x: f64, y: f64, vx: f64, vy: f64, n: usize) {
+        for _ in 0..n {
+            let theta = self.rng.random() * std::f64::consts::TAU;
+            let speed = self.rng.random() * 25.;
+            let r = self.rng.random() * 15. + 15.;
+            let puff = Puff {
+                x: x + theta.cos() * r * 0.5,
+                y: y + theta.sin() * r * 0.5,
+                vx: theta.cos() * speed + vx * 0.1,
+                vy: theta.sin() * speed + vy * 0.1,
+                r,
+                growth: self.rng.random() * 6. + 6.,
+                life: self.rng.random() * 4. + 6.,
+                opacity: 0.12,
+            };
+            self.push(puff);
+        }
+    }
+
+    /// A small puff behind a rising shell. Call it every step, it only makes one
+    /// every now and then (`rate` per second, on average)
+    pub fn trail(&mut self, x: f64, y: f64, rate: f64, dt: f64) {
+        if self.rng.random() >= rate * dt {
+            return;
+        }
+        let puff = Puff {
+            x,
+            y,
+            vx: 0.,
+            vy: 0.,
+            r: self.rng.random() * 3. + 3.,
+            growth: self.rng.random() * 3. + 4.,
+            life: self.rng.random() * 2. + 3.,
+            opacity: 0.08,
+        };
+        self.push(puff);
+    }
+
+    /// Lights up every puff within `radius` of a burst at (`x`, `y`)
+    pub fn light(&mut self, x: f64, y: f64, radius: f64, hue: f64) {
+        for i in 0..self.len() {
+            let d = ((self.x[i] - x).powi(2) + (self.y[i] - y).powi(2)).sqrt();
+            let glow = 1. - d / radius;
+            if glow > self.glow[i] {
+                self.glow[i] = glow;
+                self.hue[i] = hue;
+            }
+        }
+    }
+
+    /// Stretches every position by (`sx`, `sy`), like `Bodies::remap`
+    pub fn remap(&mut self, sx: f64, sy: f64) {
+        self.x.iter_mut().for_each(|x| *x *= sx);
+        self.y.iter_mut().for_each(|y| *y *= sy);
+    }
+
+    pub fn update(&mut self, dt: f64, air: &Air) {
+        let f = (-DRAG * air.density * dt).exp();
+        let dim = 0.5f64.powf(dt / GLOW_HALF_LIFE);
+        for i in 0..self.len() {
+            let (wx, wy) = air.wind_at(self.y[i]);
+            self.vx[i] = wx + (self.vx[i] - wx) * f;
+            self.vy[i] = wy + BUOYANCY + (self.vy[i] - wy - BUOYANCY) * f;
+            self.x[i] += self.vx[i] * dt;
+            self.y[i] += self.vy[i] * dt;
+            self.r[i] += self.growth[i] * dt;
+            self.age[i] += dt;
+            self.glow[i] *= dim;
+        }
+
+        let mut i = 0;
+        while i < self.len() {
+            if self.age[i] >= self.life[i] {
+                self.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Color and alpha of puff `i` right now. Thins out as it spreads, and gets
+    /// brighter (and tinted) when lit
+    pub fn look(&self, i: usize) -> (Color, f64) {
+        let left = 1. - self.age[i] / self.life[i];
+        let glow = self.glow[i];
+        let color = Color::new(self.hue[i], 60. * glow, 30. + 45. * glow);
+        let alpha = self.opacity[i] * left * left * (1. + glow);
+        (color, alpha)
+    }
+}
@@ -0,0 +1,247 @@
+//! Firework sounds, made up on the spot out of sines and noise so we don't have to ship
+//! any samples. Every `Sound` turns into one mono buffer of f32 PCM that starts right
+//! when the sound does, for the host to play (a WebAudio buffer) or `Mixer` to mix
+//! (a WAV file).
+//!
+//! Everything about a sound comes from its event: bigger shells thump lower, bursts
+//! boom louder and longer the more energy their stars got, and so on.
+
+use crate::audio::{AudioEvent, Sound};
+use crate::rng::Rng;
+use std::f64::consts::TAU;
+use wasm_bindgen::prelude::*;
+
+/// Burst energy of a run-of-the-mill 200 star shell, more or less. Booms are sized
+/// relative to it
+const TYPICAL_ENERGY: f64 = 100_000.;
+
+/// No single sound lasts longer than this, in seconds
+const MAX_LENGTH: f64 = 4.;
+
+/// In world units/s. About 343 m/s if the default view is 300 m tall
+pub const SPEED_OF_SOUND: f64 = 800.;
+
+/// One-pole lowpass, good enough to turn white noise into rumble
+struct Lowpass {
+    a: f64,
+    y: f64,
+}
+
+impl Lowpass {
+    fn new(cutoff: f64, rate: f64) -> Self {
+        Self { a: 1. - (-TAU * cutoff / rate).exp(), y: 0. }
+    }
+
+    #[inline]
+    fn next(&mut self, x: f64) -> f64 {
+        self.y += self.a * (x - self.y);
+        self.y
+    }
+}
+
+#[wasm_bindgen]
+pub struct Synth {
+    rate: f64, // samples per second
+    rng: Rng,
+}
+
+impl Synth {
+    /// Mono samples for `sound`, in [-1, 1]. Empty if there's nothing to hear
+    pub fn render(&mut self, sound: &Sound) -> Vec<f32> {
+        let samples = match *sound {
+            Sound::Launch { size } => self.launch(size),
+            Sound::Whistle { duration } => self.whistle(duration),
+            Sound::Burst { size, stars, energy, .. } => self.boom(size, stars, energy),
+            Sound::Crackle { stars, duration } => self.crackle(stars, duration),
+            Sound::Arrival { .. } => self.tink(),
+        };
+        samples.into_iter().map(|s| s.clamp(-1., 1.) as f32).collect()
+    }
+
+    /// How many samples `seconds` is
+    fn length(&self, seconds: f64) -> usize {
+        (seconds.clamp(0., MAX_LENGTH) * self.rate).ceil() as usize
+    }
+
+    #[inline]
+    fn noise(&mut self) -> f64 {
+        self.rng.random() * 2. - 1.
+    }
+
+    /// A quick low sine sweep (the mortar) and a puff of noise (the lift charge)
+    fn launch(&mut self, size: f64) -> Vec<f64> {
+        let size = (size / 1.5).clamp(0.5, 2.);
+        let gain = 0.4 * size.sqrt();
+        let mut lp = Lowpass::new(700., self.rate);
+        let mut phase = 0.;
+        let mut out = Vec::with_capacity(self.length(0.4));
+        for k in 0..self.length(0.4) {
+            let t = k as f64 / self.rate;
+            let freq = (35. + 120. * (-t / 0.04).exp()) / size.sqrt(); // bigger mortars go lower
+            phase += TAU * freq / self.rate;
+            let thump = phase.sin() * (-t / 0.1).exp();
+            let puff = lp.next(self.noise()) * (-t / 0.03).exp();
+            out.push(gain * (thump + 1.5 * puff));
+        }
+        out
+    }
+
+    /// A tone going up for as long as the motor burns, a bit wobbly and hissy
+    fn whistle(&mut self, duration: f64) -> Vec<f64> {
+        if duration <= 0. {
+            return Vec::new();
+        }
+        let duration = duration.min(MAX_LENGTH);
+        let mut phase = 0.;
+        let mut out = Vec::with_capacity(self.length(duration));
+        for k in 0..self.length(duration) {
+            let t = k as f64 / self.rate;
+            let p = t / duration;
+            let freq = 900. + 1800. * p * p + 25. * (TAU * 9. * t).sin();
+            phase += TAU * freq / self.rate;
+            let env = (t / 0.03).min(1.) * ((duration - t) / 0.08).min(1.);
+            out.push(env * (0.12 * phase.sin() + 0.03 * self.noise()));
+        }
+        out
+    }
+
+    /// A sharp crack, then rumble that fades slower the more energy went into the burst.
+    /// Bigger shells sound darker, and lots of stars make it rougher
+    fn boom(&mut self, size: f64, stars: usize, energy: f64) -> Vec<f64> {
+        let e = (energy / TYPICAL_ENERGY).max(0.001);
+        let size = (size / 1.5).clamp(0.5, 3.);
+        let gain = (0.45 * e.powf(0.4)).clamp(0.05, 0.7);
+        let decay = 0.1 + 0.35 * e.sqrt().min(2.); // of the rumble, in seconds
+        let roughness = (stars as f64 / 200.).clamp(0.2, 2.);
+        let sub_freq = 48. / size.sqrt();
+
+        let mut crack = Lowpass::new(4000., self.rate);
+        let (mut rumble_a, mut rumble_b) = (Lowpass::new(600. / size, self.rate), Lowpass::new(300. / size, self.rate));
+        let mut out = Vec::with_capacity(self.length(decay * 6.));
+        for k in 0..self.length(decay * 6.) {
+            let t = k as f64 / self.rate;
+            let n = self.noise();
+            let crack = crack.next(n) * (-t / 0.012).exp();
+            let grit = 1. + 0.5 * roughness * self.noise().abs();
+            let low = rumble_b.next(rumble_a.next(n * grit));
+            let rumble = 4. * low * (-t / decay).exp() * (t / 0.005).min(1.);
+            let sub = (TAU * sub_freq * t).sin() * (-t / (0.5 * decay)).exp();
+            out.push(gain * (crack + rumble + 0.6 * sub));
+        }
+        out
+    }
+
+    /// Lots of tiny pops, about one per star, spread over the stars' lives and
+    /// thinning out at the end
+    fn crackle(&mut self, stars: usize, duration: f64) -> Vec<f64> {
+        let duration = duration.clamp(0.05, MAX_LENGTH - 0.1);
+        let mut out = vec![0.; self.length(duration + 0.1)];
+        let pops = stars.min(2_000);
+        let gain = (0.25 / (pops as f64 / 100.).sqrt().max(1.)).max(0.08);
+        for _ in 0..pops {
+            let start = (self.rng.random().sqrt() * duration * self.rate) as usize;
+            let len = 0.002 + 0.004 * self.rng.random();
+            let len = self.length(len);
+            let amp = gain * (0.3 + 0.7 * self.rng.random());
+            let mut last = 0.;
+            for (k, s) in out.iter_mut().skip(start).take(len).enumerate() {
+                let n = self.rng.random() * 2. - 1.;
+                let env = 1. - k as f64 / len as f64;
+                *s += amp * env * (n - last); // crude highpass, pops are all snap
+                last = n;
+            }
+        }
+        out
+    }
+
+    /// Small bell-ish ping for lerpers landing. Just the one, however many of them did
+    fn tink(&mut self) -> Vec<f64> {
+        let freq = 1700. + 200. * self.rng.random();
+        (0..self.length(0.3))
+            .map(|k| {
+                let t = k as f64 / self.rate;
+                let env = (-t / 0.06).exp();
+                0.06 * env * ((TAU * freq * t).sin() + 0.5 * (TAU * 1.5 * freq * t).sin())
+            })
+            .collect()
+    }
+}
+
+#[wasm_bindgen]
+impl Synth {
+    /// `seed` only changes the noise, like the world's seed changes the show
+    pub fn new(sample_rate: f64, seed: u32) -> Self {
+        Self {
+            rate: sample_rate.max(1.),
+            rng: Rng::new(seed ^ 0x5359_4E54),
+        }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// `render` for one event from `World::drain_audio_events`, i.e. its
+    /// `AudioEvent::STRIDE` numbers. Empty if it isn't an event
+    pub fn render_event(&mut self, event: &[f64]) -> Vec<f32> {
+        match AudioEvent::from_array(event) {
+            Some(e) => self.render(&e.sound),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Mixes a whole show into one stereo track, for offline renders. Every event lands
+/// at the sample for its simulation time, late by however long the sound takes to get
+/// to the audience (standing at the bottom middle of the world), and panned by x
+pub struct Mixer {
+    synth: Synth,
+    listener: (f64, f64),
+    width: f64, // of the world, so the edges of the screen are all the way left or right
+    samples: Vec<f32>, // interleaved, left then right
+}
+
+impl Mixer {
+    pub fn new(sample_rate: f64, seed: u32, width: f64, height: f64) -> Self {
+        Self {
+            synth: Synth::new(sample_rate, seed),
+            listener: (width / 2., height),
+            width: width.max(1.),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, event: &AudioEvent) {
+        let sound = self.synth.render(&event.sound);
+        if sound.is_empty() {
+            return;
+        }
+
+        let distance = (event.x - self.listener.0).hypot(event.y - self.listener.1);
+        let time = event.time as f64 / 1_000_000. + distance / SPEED_OF_SOUND;
+        let start = (time * self.synth.rate).round() as usize;
+
+        // equal power, so things don't get quieter in the middle
+        let pan = ((event.x - self.listener.0) / (self.width / 2.)).clamp(-1., 1.);
+        let angle = (pan + 1.) * std::f64::consts::FRAC_PI_4;
+        let (left, right) = (angle.cos() as f32, angle.sin() as f32);
+
+        let end = 2 * (start + sound.len());
+        if self.samples.len() < end {
+            self.samples.resize(end, 0.);
+        }
+        for (frame, s) in self.samples[2 * start..end].chunks_exact_mut(2).zip(sound) {
+            frame[0] += s * left;
+            frame[1] += s * right;
+        }
+    }
+
+    /// The mix so far, cut or padded to exactly `seconds` so it's as long as the video.
+    /// Anything that piled up past full scale gets clipped
+    pub fn finish(mut self, seconds: f64) -> Vec<f32> {
+        let len = 2 * (seconds.max(0.) * self.synth.rate).round() as usize;
+        self.samples.resize(len, 0.);
+        self.samples.iter_mut().for_each(|s| *s = s.clamp(-1., 1.));
+        self.samples
+    }
+}
@@ -1,6 +1,5 @@
-use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use js_sys::Math;
+use crate::rng::Rng;
 
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
@@ -22,14 +21,12 @@ pub fn get_context(canvas_id: &str) -> web_sys::CanvasRenderingContext2d {
         .map_err(|_| ())
         .unwrap();
 
-    let context = canvas
+    canvas
         .get_context("2d")
         .unwrap()
         .unwrap()
         .dyn_into::<web_sys::CanvasRenderingContext2d>()
-        .unwrap();
-
-    context
+        .unwrap()
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -42,11 +39,100 @@ impl fmt::Display for HSL {
     }
 }
 
-pub fn random_color() -> HSL {
-    HSL(Math::random() * 360., 85., 75.)
+/// Parses the same `hsl(h,s%,l%)` format that `Display` writes
+impl std::str::FromStr for HSL {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let inner = s.trim().strip_prefix("hsl(").and_then(|s| s.strip_suffix(')')).ok_or(())?;
+        let mut parts = inner.split(',').map(|p| p.trim().trim_end_matches('%').parse::<f64>());
+        let mut next = || parts.next().ok_or(())?.map_err(|_| ());
+        Ok(HSL(next()?, next()?, next()?))
+    }
+}
+
+impl HSL {
+    /// Converts to 8 bit RGB, like the browser would (saturation and lightness are percentages)
+    pub fn to_rgb(&self) -> [u8; 3] {
+        let h = self.0.rem_euclid(360.) / 60.;
+        let s = (self.1 / 100.).clamp(0., 1.);
+        let l = (self.2 / 100.).clamp(0., 1.);
+
+        let c = (1. - (2. * l - 1.).abs()) * s;
+        let x = c * (1. - (h % 2. - 1.).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.),
+            1 => (x, c, 0.),
+            2 => (0., c, x),
+            3 => (0., x, c),
+            4 => (x, 0., c),
+            _ => (c, 0., x),
+        };
+
+        let m = l - c / 2.;
+        let to_byte = |v: f64| ((v + m) * 255.).round().clamp(0., 255.) as u8;
+        [to_byte(r), to_byte(g), to_byte(b)]
+    }
+}
+
+/// The color every body carries around. Same thing as `HSL`, but packed into 4 bytes
+/// (hue in whole degrees, saturation and lightness in whole percents), so it's cheap
+/// to copy, compare and use as a cache key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub h: u16,
+    pub s: u8,
+    pub l: u8,
+}
+
+impl Color {
+    /// Hue wraps around, saturation and lightness get clamped
+    #[inline]
+    pub fn new(h: f64, s: f64, l: f64) -> Self {
+        Self {
+            h: (h.rem_euclid(360.).round() as u16) % 360,
+            s: s.clamp(0., 100.).round() as u8,
+            l: l.clamp(0., 100.).round() as u8,
+        }
+    }
+
+    /// Unique for every color
+    #[inline]
+    pub fn key(&self) -> u32 {
+        (self.h as u32) << 16 | (self.s as u32) << 8 | self.l as u32
+    }
+
+    pub fn to_rgb(&self) -> [u8; 3] {
+        HSL::from(*self).to_rgb()
+    }
+}
+
+impl From<HSL> for Color {
+    fn from(c: HSL) -> Self {
+        Color::new(c.0, c.1, c.2)
+    }
+}
+
+impl From<Color> for HSL {
+    fn from(c: Color) -> Self {
+        HSL(c.h as f64, c.s as f64, c.l as f64)
+    }
+}
+
+/// CSS, for the canvas
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hsl({},{}%,{}%)", self.h, self.s, self.l)
+    }
+}
+
+/// `hsl(h,s%,l%)`, with an error message that says so
+pub fn parse_color(css: &str) -> Result<Color, String> {
+    css.parse::<HSL>()
+        .map(Color::from)
+        .map_err(|_| format!("can't read color {:?}, only hsl(h,s%,l%) works", css))
 }
 
-pub fn choose_from<T>(a: &[T]) -> &T {
-    let i = (Math::random() * a.len() as f64).floor() as usize;
-    &a[i]
+pub fn random_color(rng: &mut Rng) -> Color {
+    Color::new(rng.random() * 360., 85., 75.)
 }
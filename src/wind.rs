@@ -0,0 +1,74 @@
+//! Wind: a steady base plus gusts that come and go smoothly, optionally stronger
+//! higher up. Bodies don't get pushed by it directly, their drag pulls them towards
+//! moving with the air, so heavy stars barely notice and smoke goes wherever it blows.
+//!
+//! Everything is in world units/s, and it's all zero (no wind) by default.
+
+use crate::rng::Rng;
+use std::f64::consts::TAU;
+
+#[derive(Debug, Clone)]
+pub struct Wind {
+    pub x: f64, // base wind
+    pub y: f64,
+    pub gusts: f64, // how far gusts stray from the base
+    pub period: f64, // roughly how long a gust lasts, in seconds
+    pub altitude: f64, // 0 is the same everywhere, 1 is twice as strong at the top of the screen
+    phases: [f64; 3],
+}
+
+impl Wind {
+    /// Calm air. The gusts' shape comes from `seed`, so it's part of the replay
+    pub fn new(seed: u32) -> Self {
+        // its own generator, so the wind doesn't change what the world's rng rolls
+        let mut rng = Rng::new(seed ^ 0x5749_4E44);
+        Self {
+            x: 0.,
+            y: 0.,
+            gusts: 0.,
+            period: 4.,
+            altitude: 0.,
+            phases: [rng.random() * TAU, rng.random() * TAU, rng.random() * TAU],
+        }
+    }
+
+    /// Wind at ground level, `time` seconds in. A few sines at unrelated frequencies,
+    /// which is smooth and doesn't look periodic
+    pub fn at(&self, time: f64) -> (f64, f64) {
+        if self.gusts == 0. {
+            return (self.x, self.y);
+        }
+        let t = time * TAU / self.period.max(0.1);
+        let [a, b, c] = self.phases;
+        let gust = 0.6 * (t + a).sin() + 0.3 * (2.3 * t + b).sin() + 0.1 * (5.1 * t + c).sin();
+        let lift = (0.7 * t + c).sin() * 0.2; // gusts are mostly sideways
+        (self.x + self.gusts * gust, self.y + self.gusts * lift)
+    }
+
+    /// Everything the physics needs to know about the air for one step
+    pub fn air(&self, time: f64, density: f64, height: f64) -> Air {
+        Air {
+            density,
+            wind: self.at(time),
+            altitude: self.altitude,
+            height,
+        }
+    }
+}
+
+/// The air during one step
+pub struct Air {
+    pub density: f64, // multiplies every drag coefficient
+    pub wind: (f64, f64), // at ground level
+    pub altitude: f64,
+    pub height: f64, // of the screen, the ground is down there and the top is y = 0
+}
+
+impl Air {
+    /// Wind at height `y`
+    #[inline]
+    pub fn wind_at(&self, y: f64) -> (f64, f64) {
+        let scale = 1. + self.altitude * (1. - y / self.height).clamp(0., 1.);
+        (self.wind.0 * scale, self.wind.1 * scale)
+    }
+}
@@ -0,0 +1,100 @@
+use fireworks_2021_wasm::World;
+use fireworks_2021_wasm::audio::{AudioEvent, AudioQueue, Sound, MAX_QUEUED};
+
+mod common;
+use common::run_with;
+
+/// `run`, keeping every audio event
+fn listen(world: &mut World, from: f64, to: f64) -> Vec<AudioEvent> {
+    let mut events = Vec::new();
+    run_with(world, 16., from, to, |world| events.extend(world.audio_events()));
+    events
+}
+
+fn show() -> World {
+    let mut world = World::headless(1280., 720., 1);
+    let src = r#"{ "cues": [
+        { "time": 1.0, "x": 100, "vy": -600, "shell": "Strobe", "fuse": 1.0 },
+        { "time": 2.5, "x": 900, "vy": -250, "thrust": 1300, "burn": 0.5, "shell": "Chris", "fuse": "apogee" }
+    ] }"#;
+    world.load_show(0., src).unwrap();
+    world
+}
+
+#[test]
+fn shows_sound_like_they_look() {
+    let mut world = show();
+    let events = listen(&mut world, 0., 6_000.);
+    let kinds: Vec<_> = events.iter().map(|e| e.to_array()[1] as i32).collect();
+    assert_eq!(kinds, [0, 3, 2, 0, 1, 2]);
+
+    // launched on cue (well, on the first step after it), from the mortar
+    assert!(events[0].time >= 1_000_000 && events[0].time < 1_010_000);
+    assert_eq!((events[0].x, events[0].y), (100., 730.));
+    assert_eq!(events[4].sound, Sound::Whistle { duration: 0.5 });
+
+    // the first one burst a second after launch, up where the shell was
+    assert!(events[2].time >= 2_000_000 && events[2].time < 2_020_000);
+    assert!(events[2].y < 400.);
+    match events[2].sound {
+        Sound::Burst { shell, stars, energy, .. } => {
+            assert_eq!(world.shell_name(shell).as_deref(), Some("Strobe"));
+            assert!(stars >= 150 && energy > 0.);
+            assert!(matches!(events[1].sound, Sound::Crackle { stars: s, duration } if s == stars && duration > 0.5));
+        }
+        _ => unreachable!(),
+    }
+    match events[5].sound {
+        Sound::Burst { shell, .. } => assert_eq!(world.shell_name(shell).as_deref(), Some("Chris")),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn same_seed_same_sounds() {
+    let a = listen(&mut show(), 0., 6_000.);
+    let b = listen(&mut show(), 0., 6_000.);
+    assert_eq!(a, b);
+}
+
+#[test]
+fn countdown_digits_arrive() {
+    let mut world = World::headless(1280., 720., 1);
+    world.set_random_launches(false);
+    world.update_countdown(0., 1.);
+    let events = listen(&mut world, 0., 1_500.);
+
+    // the whole digit lands at once, so that's a single event, right in the middle
+    assert_eq!(events.len(), 1);
+    let e = &events[0];
+    assert!(matches!(e.sound, Sound::Arrival { count } if count > 10));
+    assert!(e.time >= 1_000_000 && e.time < 1_010_000);
+    assert!((e.x - 640.).abs() < 40. && (e.y - 360.).abs() < 40.);
+}
+
+#[test]
+fn flat_layout() {
+    let mut world = show();
+    listen(&mut world, 0., 1_100.);
+    world.loopity_loop(1_100.);
+    assert!(world.drain_audio_events().is_empty());
+
+    let mut world = show();
+    world.loopity_loop(16.);
+    world.loopity_loop(1_016.);
+    let flat = world.drain_audio_events();
+    assert_eq!(flat.len(), AudioEvent::STRIDE);
+    assert_eq!(flat[1..5], [0., 100., 730., -1.]);
+    assert!(flat[0] >= 1_000. && flat[0] < 1_010.);
+}
+
+#[test]
+fn nobody_listening() {
+    let mut queue = AudioQueue::new();
+    for i in 0..MAX_QUEUED + 10 {
+        queue.push(i as u64, 0., 0., Sound::Arrival { count: 1 });
+    }
+    assert_eq!(queue.len(), MAX_QUEUED);
+    assert_eq!(queue.drain().len(), MAX_QUEUED);
+    assert!(queue.is_empty());
+}
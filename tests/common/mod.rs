@@ -0,0 +1,30 @@
+//! Helpers shared by the integration tests. Not every test file uses all of them
+#![allow(dead_code)]
+
+use fireworks_2021_wasm::World;
+
+/// Steps the world at ~60fps from `from` to `to` (both in milliseconds)
+pub fn run(world: &mut World, from: f64, to: f64) {
+    run_at(world, 16., from, to);
+}
+
+/// `run`, with frames `frame` milliseconds apart
+pub fn run_at(world: &mut World, frame: f64, from: f64, to: f64) {
+    run_with(world, frame, from, to, |_| {});
+}
+
+/// `run_at`, calling `each` after every frame
+pub fn run_with(world: &mut World, frame: f64, from: f64, to: f64, mut each: impl FnMut(&mut World)) {
+    let mut t = from;
+    while t < to {
+        t = (t + frame).min(to);
+        world.loopity_loop(t);
+        each(world);
+    }
+}
+
+/// A show of one `shell`, launched straight up from the middle right away, that
+/// bursts `fuse` seconds later
+pub fn single_cue(shell: &str, fuse: f64) -> String {
+    format!(r#"{{ "cues": [{{ "time": 0, "x": 640, "vy": -600, "shell": "{}", "fuse": {} }}] }}"#, shell, fuse)
+}
@@ -0,0 +1,34 @@
+use fireworks_2021_wasm::entity::Entities;
+
+#[test]
+fn handles_follow_swap_removes() {
+    let mut e = Entities::new();
+    let a = e.spawn();
+    let b = e.spawn();
+    let c = e.spawn();
+    assert_eq!((e.get(a), e.get(b), e.get(c)), (Some(0), Some(1), Some(2)));
+
+    // c gets swapped into a's place
+    assert_eq!(e.despawn(a), Some(0));
+    assert_eq!(e.get(a), None);
+    assert_eq!(e.get(c), Some(0));
+    assert_eq!(e.get(b), Some(1));
+    assert_eq!(e.len(), 2);
+}
+
+#[test]
+fn stale_handles_never_match_reused_slots() {
+    let mut e = Entities::new();
+    let a = e.spawn();
+    e.despawn(a);
+
+    let b = e.spawn(); // reuses a's slot
+    assert_ne!(a, b);
+    assert_eq!(e.get(a), None);
+    assert_eq!(e.get(b), Some(0));
+
+    // despawning a stale handle does nothing
+    assert_eq!(e.despawn(a), None);
+    assert_eq!(e.get(b), Some(0));
+    assert_eq!(e.len(), 1);
+}
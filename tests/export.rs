@@ -0,0 +1,28 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use fireworks_2021_wasm::export::{write_wav, Y4mWriter};
+
+#[test]
+fn y4m_frames_are_444_planes() {
+    let mut w = Y4mWriter::new(Vec::new(), 2, 1, 30).unwrap();
+    w.write_frame(&[0, 0, 0, 255, 255, 255, 255, 255]).unwrap();
+    let out = w.into_inner();
+
+    let header = b"YUV4MPEG2 W2 H1 F30:1 Ip A1:1 C444\nFRAME\n";
+    assert_eq!(&out[..header.len()], header);
+    // Y plane: black and white in limited range, then neutral chroma
+    assert_eq!(&out[header.len()..], &[16, 235, 128, 128, 128, 128]);
+}
+
+#[test]
+fn wav_is_16_bit_pcm() {
+    let mut out = Vec::new();
+    write_wav(&mut out, 48_000, 2, &[0., 1., -1., 2.]).unwrap();
+    assert_eq!(out.len(), 44 + 8);
+    assert_eq!(&out[..4], b"RIFF");
+    assert_eq!(&out[8..16], b"WAVEfmt ");
+    assert_eq!(u32::from_le_bytes(out[24..28].try_into().unwrap()), 48_000);
+    assert_eq!(u32::from_le_bytes(out[40..44].try_into().unwrap()), 8);
+    // clipped, not wrapped around
+    assert_eq!(&out[44..], &[0, 0, 0xff, 0x7f, 0x01, 0x80, 0xff, 0x7f]);
+}
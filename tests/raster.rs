@@ -0,0 +1,124 @@
+use fireworks_2021_wasm::World;
+use fireworks_2021_wasm::raster::RasterRenderer;
+use fireworks_2021_wasm::render::{Background, Renderer};
+use fireworks_2021_wasm::utils::{Color, HSL};
+
+#[test]
+fn hsl_round_trips_through_its_css_string() {
+    let hsl: HSL = HSL(123.456, 85., 75.).to_string().parse().unwrap();
+    assert_eq!(hsl.to_string(), "hsl(123.46,85%,75%)");
+    assert!("rgb(1, 2, 3)".parse::<HSL>().is_err());
+
+    assert_eq!(HSL(0., 100., 50.).to_rgb(), [255, 0, 0]);
+    assert_eq!(HSL(120., 100., 50.).to_rgb(), [0, 255, 0]);
+    assert_eq!(HSL(240., 100., 25.).to_rgb(), [0, 0, 128]);
+    assert_eq!(HSL(42., 0., 100.).to_rgb(), [255, 255, 255]);
+}
+
+#[test]
+fn colors_pack_and_wrap() {
+    let c = Color::new(-90.4, 85., 120.);
+    assert_eq!(c, Color { h: 270, s: 85, l: 100 });
+    assert_eq!(c.to_string(), "hsl(270,85%,100%)");
+    assert_eq!(Color::new(359.7, 0., 0.).h, 0);
+    assert_ne!(Color::new(1., 2., 3.).key(), Color::new(3., 2., 1.).key());
+    assert_eq!(Color::new(120., 100., 50.).to_rgb(), [0, 255, 0]);
+}
+
+#[test]
+fn circles_are_filled_and_clipped() {
+    let mut r = RasterRenderer::new(32, 32);
+    r.circle(16., 16., 4., Color::new(0., 100., 50.));
+    assert_eq!(r.pixel(16, 16), [255, 0, 0, 255]);
+    assert_eq!(r.pixel(16, 25), [0, 0, 0, 0]);
+
+    // mostly off-screen, shouldn't panic
+    r.circle(-3., 31., 5., Color::new(0., 100., 50.));
+    assert_eq!(r.pixel(0, 31), [255, 0, 0, 255]);
+}
+
+#[test]
+fn fade_darkens_towards_opaque_black() {
+    let mut r = RasterRenderer::new(4, 4);
+    r.circle(2., 2., 10., Color::new(0., 0., 100.));
+    r.fade(4., 4., 0.2, Background::default());
+    assert_eq!(r.pixel(1, 1), [204, 204, 204, 255]);
+
+    let mut empty = RasterRenderer::new(4, 4);
+    empty.fade(4., 4., 0.2, Background::default());
+    assert_eq!(empty.pixel(3, 3), [0, 0, 0, 51]);
+}
+
+#[test]
+fn gradients_go_top_to_bottom_and_transparent_erases() {
+    let mut r = RasterRenderer::new(2, 100);
+    let sky = Background::Gradient { top: Color::new(0., 100., 50.), bottom: Color::new(240., 100., 50.) };
+    r.fade(2., 100., 1., sky);
+    assert_eq!(r.pixel(0, 0), [254, 0, 1, 255]);
+    assert_eq!(r.pixel(1, 99), [1, 0, 254, 255]);
+
+    r.fade(2., 100., 0.5, Background::Transparent);
+    assert_eq!(r.pixel(0, 0), [254, 0, 1, 128]);
+    r.fade(2., 100., 1., Background::Transparent);
+    assert_eq!(r.pixel(0, 0)[3], 0);
+}
+
+#[test]
+fn worlds_render_the_same_pixels() {
+    let render = || {
+        let mut world = World::with_renderer(320., 240., 5, Box::new(RasterRenderer::new(320, 240)));
+        world.update_countdown(0., 3.);
+        for frame in 1..=40 {
+            world.loopity_loop(frame as f64 * 25.);
+        }
+        world.frame().unwrap().to_vec()
+    };
+
+    let a = render();
+    assert_eq!(a.len(), 320 * 240 * 4);
+    assert!(a.chunks(4).any(|px| px[0] > 0));
+    assert_eq!(a, render());
+}
+
+#[test]
+fn pixels_are_straight_alpha() {
+    // half covered red over nothing is still full red, just half transparent
+    let mut r = RasterRenderer::new(1, 1);
+    r.circle(0.5, 0.5, 0.4, Color::new(0., 100., 50.));
+    let [red, g, b, a] = r.pixel(0, 0);
+    assert_eq!((red, g, b), (255, 0, 0));
+    assert!(a > 0 && a < 255);
+}
+
+#[test]
+fn the_frame_buffer_follows_the_world() {
+    let mut world = World::headless(64., 48., 1);
+    assert!(world.frame_ptr().is_null());
+    assert_eq!(world.frame_len(), 0);
+
+    world.use_pixel_renderer();
+    world.loopity_loop(16.);
+    assert_eq!((world.frame_width(), world.frame_height()), (64, 48));
+    assert_eq!(world.frame_len(), 64 * 48 * 4);
+    assert_eq!(world.frame_ptr(), world.frame().unwrap().as_ptr());
+
+    world.resize(100.5, 20.);
+    assert_eq!((world.frame_width(), world.frame_height()), (101, 20));
+    assert_eq!(world.frame_len(), 101 * 20 * 4);
+}
+
+#[test]
+fn hidpi_buffers_have_more_pixels_but_the_same_picture() {
+    let mut r = RasterRenderer::new(0, 0);
+    r.resize(10., 8., 2.);
+    assert_eq!((r.width(), r.height()), (20, 16));
+    r.circle(5., 4., 2., Color::new(0., 100., 50.));
+    assert_eq!(r.pixel(10, 8), [255, 0, 0, 255]);
+    assert_eq!(r.pixel(12, 8), [255, 0, 0, 255]); // a css pixel away, still well inside
+    assert_eq!(r.pixel(2, 2), [0, 0, 0, 0]);
+
+    let mut world = World::headless(64., 48., 1);
+    world.use_pixel_renderer();
+    world.set_pixel_ratio(1.5);
+    assert_eq!((world.frame_width(), world.frame_height()), (96, 72));
+}
@@ -0,0 +1,142 @@
+use fireworks_2021_wasm::World;
+use fireworks_2021_wasm::render::{Background, DrawCall, RecordingRenderer};
+use fireworks_2021_wasm::utils::Color;
+use std::collections::HashSet;
+
+#[test]
+fn draws_through_the_renderer() {
+    let recording = RecordingRenderer::new();
+    let mut world = World::with_renderer(800., 600., 1, Box::new(recording.clone()));
+
+    world.push_lerper(0., 400., 300., false);
+    world.loopity_loop(16.);
+
+    let calls = recording.take();
+    assert!(matches!(calls[0], DrawCall::Fade { width: 800., height: 600., background: Background::Solid(_), .. }));
+    assert_eq!(calls.len(), 2);
+    // 2 units wide, at 0.625 pixels per unit (800x600 fits 1280x960 units)
+    assert!(matches!(&calls[1], DrawCall::Circles { circles, .. } if circles.len() == 1 && circles[0].2 == 1.25));
+
+    assert!(recording.take().is_empty());
+}
+
+#[test]
+fn one_batch_per_color() {
+    let recording = RecordingRenderer::new();
+    let mut world = World::with_renderer(800., 600., 1, Box::new(recording.clone()));
+    world.update_countdown(0., 8888.);
+    world.loopity_loop(500.);
+
+    let mut colors = HashSet::new();
+    let mut drawn = 0;
+    for call in recording.take() {
+        if let DrawCall::Circles { color, circles } = call {
+            assert!(colors.insert(color), "{:?} was drawn in two batches", color);
+            drawn += circles.len();
+        }
+    }
+    assert!(colors.len() > 1);
+    assert_eq!(drawn, world.entity_count());
+}
+
+#[test]
+fn invisible_and_offscreen_bodies_are_skipped() {
+    let recording = RecordingRenderer::new();
+    let mut world = World::with_renderer(800., 600., 1, Box::new(recording.clone()));
+
+    // zero radius while they wait in the mortar
+    let show = r#"{ "cues": [
+        { "time": 5, "x": 100, "vy": -600, "shell": "Peony", "fuse": 1 },
+        { "time": 5, "x": 200, "vy": -600, "shell": "Peony", "fuse": 1 }
+    ] }"#;
+    world.load_show(0., show).unwrap();
+    // and this one's target is way off to the left
+    world.push_lerper(0., -500., 300., false);
+
+    world.loopity_loop(990.);
+    let calls = recording.take();
+    assert_eq!(world.entity_count(), 3);
+    assert_eq!(calls.len(), 1); // just the fade
+}
+
+/// How much of a trail is left after drawing until `to` ms, `frame` ms at a time
+fn trail_left(world: &mut World, recording: &RecordingRenderer, frame: f64, to: f64) -> f64 {
+    let mut left = 1.;
+    let mut t = 0.;
+    while t < to {
+        t += frame;
+        world.loopity_loop(t);
+        for call in recording.take() {
+            if let DrawCall::Fade { alpha, .. } = call {
+                left *= 1. - alpha;
+            }
+        }
+    }
+    left
+}
+
+#[test]
+fn trails_fade_with_time_not_frames() {
+    let recording = RecordingRenderer::new();
+    let mut world = World::with_renderer(800., 600., 1, Box::new(recording.clone()));
+    world.set_trail_half_life(0.25);
+
+    let slow = trail_left(&mut world, &recording, 50., 500.);
+    let mut world = World::with_renderer(800., 600., 1, Box::new(recording.clone()));
+    world.set_trail_half_life(0.25);
+    let fast = trail_left(&mut world, &recording, 10., 500.);
+
+    assert!((slow - 0.25).abs() < 1e-9, "{}", slow);
+    assert!((fast - 0.25).abs() < 1e-9, "{}", fast);
+
+    // and no trails at all clears everything, every frame
+    world.set_trail_half_life(0.);
+    world.loopity_loop(510.);
+    assert!(matches!(recording.take()[0], DrawCall::Fade { alpha, .. } if alpha == 1.));
+}
+
+#[test]
+fn backgrounds_are_passed_along() {
+    let recording = RecordingRenderer::new();
+    let mut world = World::with_renderer(800., 600., 1, Box::new(recording.clone()));
+
+    world.set_background_gradient("hsl(230,60%,5%)", "hsl(260,50%,20%)").unwrap();
+    world.loopity_loop(16.);
+    let top = Color::new(230., 60., 5.);
+    let bottom = Color::new(260., 50., 20.);
+    assert!(matches!(recording.take()[0], DrawCall::Fade { background, .. } if background == Background::Gradient { top, bottom }));
+
+    assert!(world.set_background_color("black").is_err());
+    world.set_background_transparent();
+    world.loopity_loop(32.);
+    assert!(matches!(recording.take()[0], DrawCall::Fade { background: Background::Transparent, .. }));
+}
+
+#[test]
+fn bigger_screens_draw_the_same_show_bigger() {
+    let draw = |width: f64, height: f64| {
+        let recording = RecordingRenderer::new();
+        let mut world = World::with_renderer(width, height, 21, Box::new(recording.clone()));
+        world.set_smoke(false);
+        world.update_countdown(0., 0.);
+        for frame in 1..=300 {
+            world.loopity_loop(frame as f64 * 16.);
+        }
+        recording.take()
+    };
+
+    let small = draw(1280., 720.);
+    let big = draw(3840., 2160.);
+    assert_eq!(small.len(), big.len());
+    for (a, b) in small.iter().zip(big.iter()) {
+        match (a, b) {
+            (DrawCall::Circles { circles: a, .. }, DrawCall::Circles { circles: b, .. }) => {
+                for (a, b) in a.iter().zip(b) {
+                    assert!((a.0 * 3. - b.0).abs() < 1e-6 && (a.1 * 3. - b.1).abs() < 1e-6 && (a.2 * 3. - b.2).abs() < 1e-9);
+                }
+            }
+            (DrawCall::Fade { width, .. }, DrawCall::Fade { width: big, .. }) => assert_eq!(width * 3., *big),
+            _ => panic!("{:?} and {:?}", a, b),
+        }
+    }
+}
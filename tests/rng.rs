@@ -0,0 +1,19 @@
+use fireworks_2021_wasm::rng::Rng;
+
+#[test]
+fn same_seed_same_sequence() {
+    let mut a = Rng::new(2022);
+    let mut b = Rng::new(2022);
+    for _ in 0..1000 {
+        assert_eq!(a.random(), b.random());
+    }
+}
+
+#[test]
+fn random_is_in_unit_interval() {
+    let mut rng = Rng::new(7);
+    for _ in 0..10_000 {
+        let x = rng.random();
+        assert!((0.0..1.0).contains(&x));
+    }
+}
@@ -0,0 +1,119 @@
+use fireworks_2021_wasm::World;
+
+mod common;
+use common::{run, single_cue};
+
+fn launch(world: &mut World, shell: &str) {
+    world.load_show(0., &single_cue(shell, 1.)).unwrap();
+}
+
+#[test]
+fn custom_shells_burst_as_specified() {
+    let mut world = World::headless(1280., 720., 1);
+    let spec = r#"[{
+        "name": "Tiny",
+        "layers": [
+            { "count": [10, 10], "force": [100, 200], "fuse": [5, 5] },
+            { "count": [3, 3], "force": [50, 50], "fuse": [5, 5], "star": { "type": "Glitter", "freq": [1, 2] } }
+        ]
+    }]"#;
+    assert_eq!(world.load_shells(spec), Ok(1));
+
+    launch(&mut world, "Tiny");
+    run(&mut world, 0., 1100.);
+    assert_eq!(world.entity_count(), 13);
+    assert_eq!(world.pending_fuses(), 13);
+}
+
+#[test]
+fn specs_with_the_same_name_replace_builtins() {
+    let mut world = World::headless(1280., 720., 1);
+    world.load_shells(r#"[{ "name": "Peony", "layers": [{ "count": [2, 2], "force": [100, 100] }] }]"#).unwrap();
+
+    launch(&mut world, "Peony");
+    run(&mut world, 0., 1100.);
+    assert_eq!(world.entity_count(), 2);
+}
+
+#[test]
+fn bad_specs_are_rejected() {
+    let mut world = World::headless(1280., 720., 1);
+    assert!(world.load_shells("[{").is_err());
+    assert!(world.load_shells(r#"[{ "name": "A", "layers": [] }]"#).is_err());
+    assert!(world.load_shells(r#"[{ "name": "A", "layers": [{ "count": [5, 1], "force": [1, 1] }] }]"#).is_err());
+    assert!(world.load_shells(r#"[{ "name": "A", "layers": [{ "count": [1, 1], "force": [1, 1], "fuse": [1e300, 1e300] }] }]"#).is_err());
+    assert!(world.load_shells(r#"[{ "name": "A", "layers": [{ "count": [1, 1], "force": [1, 1e300] }] }]"#).is_err());
+    assert!(world.load_shells(r#"[{ "name": "A", "layers": [{ "count": [1, 1e9], "force": [1, 1] }] }]"#).is_err());
+    assert!(world.load_shells(r#"[{ "name": "Comet", "layers": [{ "count": [1, 1], "force": [1, 1] }] }]"#).is_err());
+
+    let err = world.load_shells(r#"[{
+        "name": "A",
+        "layers": [{ "count": [1, 1], "force": [1, 1], "star": { "type": "Shell", "name": "Nope" } }]
+    }]"#);
+    assert_eq!(err, Err("A: unknown star shell \"Nope\"".to_string()));
+
+    // nothing from the failed loads stuck around
+    assert!(world.load_show(0., r#"{ "cues": [{ "time": 0, "x": 0, "vy": 0, "shell": "A", "fuse": 1 }] }"#).is_err());
+}
+
+#[test]
+fn stages_nest_to_any_depth() {
+    let mut world = World::headless(1280., 720., 1);
+    let spec = r#"[{
+        "name": "ThreeStage",
+        "layers": [{
+            "count": [2, 2], "force": [100, 100], "fuse": [1, 1],
+            "star": { "type": "Stage", "layers": [{
+                "count": [3, 3], "force": [100, 100], "fuse": [1, 1],
+                "star": { "type": "Stage", "layers": [{ "count": [4, 4], "force": [100, 100], "fuse": [5, 5] }] }
+            }] }
+        }]
+    }]"#;
+    assert_eq!(world.load_shells(spec), Ok(3));
+
+    launch(&mut world, "ThreeStage");
+    run(&mut world, 0., 1100.);
+    assert_eq!(world.entity_count(), 2);
+    run(&mut world, 1100., 2100.);
+    assert_eq!(world.entity_count(), 2 * 3);
+    run(&mut world, 2100., 3100.);
+    assert_eq!(world.entity_count(), 2 * 3 * 4);
+}
+
+#[test]
+fn loops_and_huge_shells_are_rejected() {
+    let mut world = World::headless(1280., 720., 1);
+    let looping = r#"[
+        { "name": "A", "layers": [{ "count": [1, 1], "force": [1, 1], "star": { "type": "Shell", "name": "B" } }] },
+        { "name": "B", "layers": [{ "count": [1, 1], "force": [1, 1], "star": { "type": "Shell", "name": "A" } }] }
+    ]"#;
+    assert!(world.load_shells(looping).unwrap_err().contains("loop"));
+
+    let huge = r#"[{ "name": "Huge", "layers": [{
+        "count": [300, 300], "force": [1, 1],
+        "star": { "type": "Stage", "layers": [{ "count": [300, 300], "force": [1, 1] }] }
+    }] }]"#;
+    assert!(world.load_shells(huge).unwrap_err().contains("stars"));
+}
+
+#[test]
+fn comets_can_be_stars() {
+    let mut world = World::headless(1280., 720., 1);
+    let spec = r#"[{ "name": "Comets", "layers": [{
+        "count": [3, 3], "force": [200, 200], "fuse": [0.1, 0.1],
+        "star": { "type": "Shell", "name": "Comet" }
+    }] }]"#;
+    assert_eq!(world.load_shells(spec), Ok(1));
+
+    launch(&mut world, "Comets");
+    run(&mut world, 0., 1_100.);
+    assert_eq!(world.entity_count(), 3);
+    // each one is throwing out its own sparks a moment later
+    run(&mut world, 1_100., 1_500.);
+    assert!(world.entity_count() > 3 * 5);
+
+    let huge = r#"[{ "name": "Huge", "layers": [{
+        "count": [400, 400], "force": [1, 1], "star": { "type": "Shell", "name": "Comet" }
+    }] }]"#;
+    assert!(world.load_shells(huge).unwrap_err().contains("stars"));
+}
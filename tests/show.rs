@@ -0,0 +1,104 @@
+use fireworks_2021_wasm::World;
+
+mod common;
+use common::{run, single_cue};
+
+#[test]
+fn cues_launch_on_time() {
+    let mut world = World::headless(1280., 720., 1);
+    let src = r#"{ "cues": [
+        { "time": 1.0, "x": 100, "y": 700, "vx": 0, "vy": -600, "shell": "Chris", "color": 10, "fuse": 1.0 },
+        { "time": 3.0, "x": 900, "vy": -600, "shell": "Peony", "fuse": 1.0 }
+    ] }"#;
+    assert_eq!(world.load_show(0., src), Ok(2));
+    assert_eq!(world.entity_count(), 2);
+    assert_eq!(world.pending_fuses(), 2);
+
+    // still in the mortar
+    run(&mut world, 0., 900.);
+    assert_eq!(world.positions()[0], (100., 700.));
+
+    // launched, going up, not burst yet
+    run(&mut world, 900., 1500.);
+    assert!(world.positions()[0].1 < 700.);
+    assert_eq!(world.entity_count(), 2);
+
+    // first one burst
+    run(&mut world, 1500., 2100.);
+    assert!(world.entity_count() > 100);
+}
+
+#[test]
+fn bad_shows_are_rejected() {
+    let mut world = World::headless(1280., 720., 1);
+    assert!(world.load_show(0., "{").is_err());
+    assert!(world.load_show(0., r#"{ "cues": [{ "time": -1, "x": 0, "vy": 0, "shell": "Peony", "fuse": 1 }] }"#).is_err());
+
+    let err = world.load_show(0., r#"{ "cues": [{ "time": 0, "x": 0, "vy": 0, "shell": "Banana", "fuse": 1 }] }"#);
+    assert_eq!(err, Err("unknown shell \"Banana\"".to_string()));
+    assert!(world.load_show(0., r#"{ "cues": [{ "time": 0, "x": 0, "vy": 0, "shell": "Peony", "color": "red", "fuse": 1 }] }"#).is_err());
+    assert!(world.load_show(0., r#"{ "cues": [{ "time": 0, "x": 0, "vy": 0, "shell": "Peony", "fuse": "later" }] }"#).is_err());
+    assert!(world.load_show(0., r#"{ "cues": [{ "time": 1e300, "x": 0, "vy": 0, "shell": "Peony", "fuse": 1 }] }"#).is_err());
+    assert!(world.load_show(0., r#"{ "cues": [{ "time": 0, "x": 0, "vy": 0, "shell": "Peony", "fuse": 1e300 }] }"#).is_err());
+    assert!(world.load_show(0., r#"{ "cues": [{ "time": 0, "x": 0, "vy": 0, "shell": "Peony", "burn": 1e300, "fuse": 1 }] }"#).is_err());
+    assert!(world.load_show(0., r#"{ "cues": [{ "time": 0, "x": 0, "vy": 0, "shell": "Peony", "size": -1, "fuse": 1 }] }"#).is_err());
+    assert_eq!(world.entity_count(), 0);
+}
+
+#[test]
+fn scripted_shows_replace_random_launches() {
+    let src = include_str!("../shows/example.json");
+    let mut world = World::headless(1280., 720., 1);
+    world.update_countdown(0., 0.);
+    world.load_show(0., src).unwrap();
+
+    // after everything burst and died, nothing random should be flying
+    run(&mut world, 0., 20_000.);
+    assert_eq!(world.entity_count(), 0);
+}
+
+/// How wide a single Peony is, 0.6s after it bursts, in air of some `density`
+fn peony_spread(density: f64) -> f64 {
+    let mut world = World::headless(1280., 720., 3);
+    world.set_air_density(density);
+    world.load_show(0., &single_cue("Peony", 1.)).unwrap();
+    run(&mut world, 0., 1_600.);
+
+    let xs: Vec<f64> = world.positions().iter().map(|p| p.0).collect();
+    assert!(xs.len() > 10);
+    xs.iter().cloned().fold(f64::MIN, f64::max) - xs.iter().cloned().fold(f64::MAX, f64::min)
+}
+
+#[test]
+fn thicker_air_keeps_bursts_tighter() {
+    let vacuum = peony_spread(0.);
+    let normal = peony_spread(1.);
+    let thick = peony_spread(3.);
+    assert!(vacuum > normal && normal > thick, "{} {} {}", vacuum, normal, thick);
+}
+
+#[test]
+fn motors_burn_then_coast_to_apogee() {
+    let mut world = World::headless(1280., 720., 1);
+    world.set_smoke(false);
+    let src = r#"{ "cues": [{ "time": 0, "x": 640, "y": 700, "vy": -200, "thrust": 1300, "burn": 0.5, "shell": "Peony", "fuse": "apogee" }] }"#;
+    world.load_show(0., src).unwrap();
+
+    // sparks out the back while the motor burns
+    run(&mut world, 0., 400.);
+    assert!(world.entity_count() > 5);
+
+    // then just the shell, still going up. 800px/s² net for 0.5s, then 500px/s² of gravity
+    // puts the top at 700 - 200 - 360 = 140, 1.7s in
+    run(&mut world, 400., 1_200.);
+    assert_eq!(world.entity_count(), 1);
+    let mut top = f64::MAX;
+    let mut t = 1_200.;
+    while world.entity_count() == 1 {
+        top = top.min(world.positions()[0].1);
+        t += 16.;
+        world.loopity_loop(t);
+    }
+    assert!((1_650. ..1_800.).contains(&t), "burst at {}ms", t);
+    assert!((top - 140.).abs() < 10., "topped out at {}", top);
+}
@@ -0,0 +1,88 @@
+use fireworks_2021_wasm::World;
+use fireworks_2021_wasm::render::{DrawCall, RecordingRenderer};
+use fireworks_2021_wasm::smoke::{Smoke, MAX_PUFFS};
+use fireworks_2021_wasm::wind::Wind;
+
+mod common;
+use common::{run, run_with, single_cue};
+
+#[test]
+fn puffs_grow_drift_and_clear_up() {
+    let mut smoke = Smoke::new(1);
+    smoke.burst(500., 300., 0., 0., 5);
+    assert_eq!(smoke.len(), 5);
+    let (r, alpha) = (smoke.r[0], smoke.look(0).1);
+
+    let mut wind = Wind::new(1);
+    wind.x = 40.;
+    let air = wind.air(0., 1., 720.);
+    for _ in 0..120 {
+        smoke.update(1. / 60., &air);
+    }
+    assert!(smoke.r[0] > r);
+    assert!(smoke.look(0).1 < alpha);
+    assert!(smoke.x.iter().sum::<f64>() / 5. > 520.);
+
+    for _ in 0..60 * 10 {
+        smoke.update(1. / 60., &air);
+    }
+    assert!(smoke.is_empty());
+}
+
+#[test]
+fn there_is_only_so_much_smoke() {
+    let mut smoke = Smoke::new(1);
+    for _ in 0..MAX_PUFFS {
+        smoke.burst(0., 0., 0., 0., 3);
+    }
+    assert_eq!(smoke.len(), MAX_PUFFS);
+}
+
+#[test]
+fn bursts_light_up_nearby_smoke() {
+    let mut smoke = Smoke::new(1);
+    smoke.burst(100., 100., 0., 0., 1);
+    smoke.burst(900., 100., 0., 0., 1);
+    let dark = smoke.look(0);
+
+    smoke.light(100., 100., 250., 120.);
+    let lit = smoke.look(0);
+    assert!(lit.0.l > dark.0.l && lit.1 > dark.1);
+    assert_eq!(smoke.look(1), dark);
+}
+
+#[test]
+fn smoke_is_drawn_behind_the_sparks() {
+    let recording = RecordingRenderer::new();
+    let mut world = World::with_renderer(1280., 720., 1, Box::new(recording.clone()));
+    world.load_show(0., &single_cue("Peony", 1.)).unwrap();
+    run(&mut world, 0., 1_200.);
+    assert!(world.smoke_count() > 4);
+
+    let calls = recording.take();
+    let frame = &calls[calls.iter().rposition(|c| matches!(c, DrawCall::Fade { .. })).unwrap()..];
+    let first_spark = frame.iter().position(|c| matches!(c, DrawCall::Circles { .. })).unwrap();
+    let last_puff = frame.iter().rposition(|c| matches!(c, DrawCall::Puff { .. })).unwrap();
+    assert!(last_puff < first_spark);
+
+    // and none of it if we don't want it
+    let mut world = World::headless(1280., 720., 1);
+    world.set_smoke(false);
+    world.load_show(0., &single_cue("Peony", 1.)).unwrap();
+    run(&mut world, 0., 1_200.);
+    assert_eq!(world.smoke_count(), 0);
+}
+
+#[test]
+fn only_shells_from_the_ground_make_smoke() {
+    // a StrobePeony's stars all burst again, but that's still one shell's worth of smoke
+    let mut most = 0;
+    for shell in ["Chris", "StrobePeony"] {
+        let mut world = World::headless(1280., 720., 1);
+        world.load_show(0., &single_cue(shell, 1.)).unwrap();
+        let mut peak = 0;
+        run_with(&mut world, 16., 0., 4_000., |world| peak = peak.max(world.smoke_count()));
+        most = most.max(peak);
+    }
+    assert!(most < 40, "{} puffs", most);
+}
@@ -0,0 +1,123 @@
+use fireworks_2021_wasm::audio::{AudioEvent, Sound};
+use fireworks_2021_wasm::World;
+use fireworks_2021_wasm::synth::{Mixer, Synth, SPEED_OF_SOUND};
+
+const RATE: f64 = 48_000.;
+
+fn boom(energy: f64) -> Sound {
+    Sound::Burst { shell: 0, size: 1.3, stars: 200, energy }
+}
+
+fn loudness(samples: &[f32]) -> f64 {
+    samples.iter().map(|&s| (s * s) as f64).sum::<f64>().sqrt()
+}
+
+/// Zero crossings per second in `samples`, which is about twice the pitch
+fn crossings(samples: &[f32]) -> f64 {
+    let n = samples.windows(2).filter(|w| (w[0] < 0.) != (w[1] < 0.)).count();
+    n as f64 * RATE / samples.len() as f64
+}
+
+#[test]
+fn every_sound_is_playable() {
+    let mut synth = Synth::new(RATE, 1);
+    let sounds = [
+        Sound::Launch { size: 1.3 },
+        Sound::Whistle { duration: 0.5 },
+        boom(100_000.),
+        boom(1e12), // nothing should clip its way out of [-1, 1]
+        Sound::Crackle { stars: 250, duration: 1. },
+        Sound::Arrival { count: 1 },
+    ];
+    for sound in sounds.iter() {
+        let samples = synth.render(sound);
+        assert!(!samples.is_empty(), "{:?}", sound);
+        assert!(samples.len() <= 4 * RATE as usize);
+        assert!(samples.iter().all(|s| s.is_finite() && s.abs() <= 1.), "{:?}", sound);
+        assert!(loudness(&samples) > 0.);
+    }
+    assert!(synth.render(&Sound::Whistle { duration: 0. }).is_empty());
+}
+
+#[test]
+fn bigger_bursts_boom_louder_and_longer() {
+    let mut synth = Synth::new(RATE, 1);
+    let small = synth.render(&boom(500.));
+    let big = synth.render(&boom(150_000.));
+    assert!(big.len() > small.len());
+    assert!(loudness(&big) > 2. * loudness(&small));
+}
+
+#[test]
+fn whistles_rise_for_as_long_as_the_motor_burns() {
+    let mut synth = Synth::new(RATE, 1);
+    let samples = synth.render(&Sound::Whistle { duration: 1. });
+    assert_eq!(samples.len(), RATE as usize);
+    let quarter = samples.len() / 4;
+    assert!(crossings(&samples[3 * quarter..]) > 1.5 * crossings(&samples[..quarter]));
+}
+
+#[test]
+fn events_from_js() {
+    let event = AudioEvent { time: 1_500_000, x: 10., y: 20., sound: boom(100_000.) };
+    assert_eq!(AudioEvent::from_array(&event.to_array()), Some(event.clone()));
+    assert_eq!(AudioEvent::from_array(&[0.; 3]), None);
+
+    let mut a = Synth::new(RATE, 7);
+    let mut b = Synth::new(RATE, 7);
+    assert_eq!(a.render_event(&event.to_array()), b.render(&event.sound));
+    assert!(a.render_event(&[0., 9., 0., 0., -1., 0., 0., 0.]).is_empty());
+}
+
+/// Index of the first stereo frame that isn't silent
+fn onset(samples: &[f32]) -> usize {
+    samples.iter().position(|&s| s != 0.).unwrap() / 2
+}
+
+#[test]
+fn far_away_sounds_arrive_late() {
+    // the audience is at (640, 720)
+    let mut near = Mixer::new(RATE, 1, 1280., 720.);
+    near.add(&AudioEvent { time: 1_000_000, x: 640., y: 720., sound: Sound::Launch { size: 1.3 } });
+    assert_eq!(onset(&near.finish(3.)), RATE as usize);
+
+    let mut far = Mixer::new(RATE, 1, 1280., 720.);
+    far.add(&AudioEvent { time: 1_000_000, x: 640., y: 720. - SPEED_OF_SOUND / 2., sound: Sound::Launch { size: 1.3 } });
+    let samples = far.finish(3.);
+    assert_eq!(samples.len(), 2 * 3 * RATE as usize);
+    assert_eq!(onset(&samples), (1.5 * RATE) as usize);
+}
+
+#[test]
+fn panned_by_x() {
+    let mut mixer = Mixer::new(RATE, 1, 1280., 720.);
+    mixer.add(&AudioEvent { time: 0, x: 0., y: 720., sound: Sound::Launch { size: 1.3 } });
+    let samples = mixer.finish(1.);
+    let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+    let right: Vec<f32> = samples.iter().skip(1).step_by(2).copied().collect();
+    assert!(loudness(&left) > 0.);
+    assert!(loudness(&right) < 1e-3 * loudness(&left));
+}
+
+#[test]
+fn shows_get_a_soundtrack() {
+    let mut world = World::headless(1280., 720., 1);
+    let src = r#"{ "cues": [{ "time": 1.0, "x": 640, "y": 720, "vy": -600, "shell": "Chris", "fuse": 1.0 }] }"#;
+    world.load_show(0., src).unwrap();
+
+    let mut mixer = Mixer::new(RATE, 1, world.world_width(), world.world_height());
+    for frame in 0..4 * 60 {
+        world.loopity_loop(frame as f64 * 1000. / 60.);
+        for event in world.audio_events() {
+            mixer.add(&event);
+        }
+    }
+    let samples = mixer.finish(4.);
+
+    // the launch is right where the audience is, so it's heard on the first step after the cue
+    let start = onset(&samples) as f64 / RATE;
+    assert!((1.0..1.01).contains(&start), "{}", start);
+    // and the burst, up in the sky, a bit after it happened
+    let burst = &samples[2 * (2.5 * RATE) as usize..];
+    assert!(loudness(burst) > 0.);
+}
@@ -0,0 +1,46 @@
+use fireworks_2021_wasm::World;
+use fireworks_2021_wasm::wind::Wind;
+
+mod common;
+use common::{run, single_cue};
+
+/// Average x of a Peony burst at x = 640, 1s after it bursts
+fn peony_center(wind: f64) -> f64 {
+    let mut world = World::headless(1280., 720., 7);
+    world.set_wind(wind, 0.);
+    world.load_show(0., &single_cue("Peony", 1.)).unwrap();
+    run(&mut world, 0., 2_000.);
+
+    let positions = world.positions();
+    positions.iter().map(|p| p.0).sum::<f64>() / positions.len() as f64
+}
+
+#[test]
+fn stars_drift_downwind() {
+    let calm = peony_center(0.);
+    assert!(peony_center(150.) > calm + 50.);
+    assert!(peony_center(-150.) < calm - 50.);
+}
+
+#[test]
+fn gusts_are_smooth_and_part_of_the_seed() {
+    let mut wind = Wind::new(1);
+    wind.x = 30.;
+    assert_eq!(wind.at(12.), (30., 0.));
+
+    wind.gusts = 100.;
+    let mut last = wind.at(0.);
+    let mut strongest: f64 = 0.;
+    for i in 1..=1000 {
+        let now = wind.at(i as f64 * 0.01);
+        assert!((now.0 - last.0).abs() < 5., "gusts shouldn't jump");
+        strongest = strongest.max((now.0 - 30.).abs());
+        last = now;
+    }
+    assert!(strongest > 20.);
+
+    let mut again = Wind::new(1);
+    again.x = 30.;
+    again.gusts = 100.;
+    assert_eq!(wind.at(3.3), again.at(3.3));
+}
@@ -0,0 +1,149 @@
+use fireworks_2021_wasm::World;
+
+mod common;
+use common::{run, run_at, run_with, single_cue};
+
+#[test]
+fn countdown_spawns_one_lerper_per_point() {
+    let mut world = World::headless(1280., 720., 1);
+    assert_eq!(world.entity_count(), 0);
+
+    world.update_countdown(0., 5.);
+    let n = world.entity_count();
+    assert!(n > 0);
+    assert_eq!(world.pending_fuses(), n);
+
+    // same second, nothing new
+    world.update_countdown(100., 5.2);
+    assert_eq!(world.entity_count(), n);
+}
+
+#[test]
+fn lerper_arrives_at_its_target() {
+    let mut world = World::headless(1280., 720., 2);
+    world.push_lerper(0., 300., 200., false);
+
+    // countdown lerpers take exactly one second
+    run(&mut world, 0., 990.);
+    let (x, y) = world.positions()[0];
+    assert!((x - 300.).abs() < 1., "x = {}", x);
+    assert!((y - 200.).abs() < 1., "y = {}", y);
+}
+
+#[test]
+fn fuses_are_processed_until_everything_dies() {
+    let mut world = World::headless(1280., 720., 3);
+    world.update_countdown(0., 7.);
+    let n = world.entity_count();
+
+    // lerpers fuse after 1s and become particles, which keep their own fuse
+    run(&mut world, 0., 1020.);
+    assert_eq!(world.entity_count(), n);
+    assert_eq!(world.pending_fuses(), n);
+
+    // particle fuses are 0.5-1.5s long
+    run(&mut world, 1020., 3000.);
+    assert_eq!(world.entity_count(), 0);
+    assert_eq!(world.pending_fuses(), 0);
+}
+
+#[test]
+fn same_seed_same_show() {
+    let mut a = World::headless(1280., 720., 42);
+    let mut b = World::headless(1280., 720., 42);
+    for world in [&mut a, &mut b] {
+        world.update_countdown(0., 0.);
+        run(world, 0., 10_000.);
+    }
+    assert!(a.entity_count() > 0);
+    assert_eq!(a.positions(), b.positions());
+}
+
+#[test]
+fn frame_rate_does_not_change_the_show() {
+    let mut slow = World::headless(1280., 720., 9);
+    let mut fast = World::headless(1280., 720., 9);
+    slow.update_countdown(0., 0.);
+    fast.update_countdown(0., 0.);
+
+    run_at(&mut slow, 1000. / 30., 0., 8_000.);
+    run_at(&mut fast, 1000. / 144., 0., 8_000.);
+    assert!(slow.entity_count() > 0);
+    assert_eq!(slow.positions(), fast.positions());
+}
+
+#[test]
+fn bursts_stay_under_the_particle_budget() {
+    let mut world = World::headless(1280., 720., 11);
+    world.set_max_particles(300);
+    world.update_countdown(0., 0.);
+    run(&mut world, 0., 1_500.); // let the "2024" lerpers burst and die down
+
+    let mut peak = 0;
+    run_with(&mut world, 16., 1_500., 20_000., |world| peak = peak.max(world.entity_count()));
+    assert!(peak <= 300, "{} bodies alive", peak);
+    assert!(peak > 100, "the budget shouldn't stop the show, peak was {}", peak);
+}
+
+#[test]
+fn no_particle_budget_at_all() {
+    let mut world = World::headless(1280., 720., 1);
+    world.set_max_particles(usize::MAX);
+    world.load_show(0., &single_cue("Chris", 1.)).unwrap();
+    run(&mut world, 0., 1_100.);
+    assert!(world.entity_count() > 100);
+}
+
+#[test]
+fn slow_frames_lower_the_quality_and_fast_ones_bring_it_back() {
+    let mut world = World::headless(1280., 720., 1);
+    world.set_frame_budget(10.);
+    assert_eq!(world.quality(), 1.);
+
+    for _ in 0..100 {
+        world.report_frame_time(40.);
+    }
+    assert_eq!(world.quality(), 0.25);
+
+    for _ in 0..200 {
+        world.report_frame_time(2.);
+    }
+    assert_eq!(world.quality(), 1.);
+}
+
+#[test]
+fn portrait_screens_get_a_taller_world() {
+    let mut world = World::headless(720., 1280., 4);
+    assert_eq!(world.world_width(), 1280.);
+    assert!((world.world_height() - 1280. * 1280. / 720.).abs() < 1e-9);
+    assert_eq!(world.scale(), 720. / 1280.);
+
+    // and shells still make it up most of the way
+    world.set_smoke(false);
+    world.update_countdown(0., 0.);
+    run(&mut world, 0., 2_000.);
+    let mut top = f64::MAX;
+    run_with(&mut world, 16., 2_000., 15_000., |world| {
+        top = world.positions().iter().fold(top, |top, p| top.min(p.1));
+    });
+    assert!(top < world.world_height() * 0.4, "nothing got above {}", top);
+
+    world.resize(1920., 1080.);
+    assert_eq!((world.world_width(), world.world_height()), (1280., 720.));
+}
+
+#[test]
+fn countdown_text_stays_centered_through_a_resize() {
+    let mut world = World::headless(1280., 720., 1);
+    world.update_countdown(0., 8.);
+    run(&mut world, 0., 300.);
+
+    world.resize(1280., 1000.); // same width, so 1280x1000 units
+    assert_eq!(world.world_height(), 1000.);
+    run(&mut world, 300., 990.);
+
+    let positions = world.positions();
+    let n = positions.len() as f64;
+    let (cx, cy) = positions.iter().fold((0., 0.), |(x, y), p| (x + p.0 / n, y + p.1 / n));
+    assert!((cx - 640.).abs() < 20. && (cy - 500.).abs() < 20., "text is centered at ({}, {})", cx, cy);
+}
@@ -1,4 +1,5 @@
 import * as wasm from "fireworks-2021-wasm";
+import { memory } from "fireworks-2021-wasm/fireworks_2021_wasm_bg.wasm";
 
 const fps = 45;
 const mspf = 1000 / fps;
@@ -9,14 +10,40 @@ const window_dimensions = () => [
 	document.documentElement.clientHeight || window.innerHeight,
 ];
 
-let world = wasm.World.new(...window_dimensions());
+// ?seed=1234 replays a specific show
+const params = new URLSearchParams(location.search);
+const seed = params.get('seed');
+let world = seed !== null
+	? wasm.World.with_seed(...window_dimensions(), Number(seed))
+	: wasm.World.new(...window_dimensions());
 
+// the world sizes the canvas' backing store itself, css stretches it over the page
 const canvas = document.getElementById('world');
-[canvas.width, canvas.height] = window_dimensions();
+world.set_pixel_ratio(window.devicePixelRatio || 1);
+
+// ?trail=0.2 sets how long trails last (half-life in seconds, 0 for none)
+if (params.has('trail'))
+	world.set_trail_half_life(Number(params.get('trail')));
+
+// ?pixels rasterizes in wasm and blits the whole frame at once
+const pixels = params.has('pixels');
+const ctx = canvas.getContext('2d');
+if (pixels)
+	world.use_pixel_renderer();
+
+const blit = () => {
+	const [w, h] = [world.frame_width(), world.frame_height()];
+	if (canvas.width != w || canvas.height != h)
+		[canvas.width, canvas.height] = [w, h];
+
+	// memory.buffer gets replaced whenever wasm memory grows, so no caching this view
+	const data = new Uint8ClampedArray(memory.buffer, world.frame_ptr(), world.frame_len());
+	ctx.putImageData(new ImageData(data, w, h), 0, 0);
+};
 
 window.addEventListener('resize', () => {
 	world.resize(...window_dimensions());
-	[canvas.width, canvas.height] = window_dimensions();
+	world.set_pixel_ratio(window.devicePixelRatio || 1); // zooming or changing screens
 });
 
 window['world'] = world;
@@ -106,23 +133,76 @@ window['measureHistory'] = history;
 
 ////////////////////////////////////////////////////////////////////
 
+// sound starts with the first click or key press, browsers won't play anything before
+// that. ?quiet keeps it off
+const quiet = params.has('quiet');
+let audio = null;
+let synth = null;
+const start_audio = () => {
+	if (audio || quiet) return;
+	audio = new AudioContext();
+	synth = wasm.Synth.new(audio.sampleRate, world.seed());
+};
+window.addEventListener('mousedown', start_audio);
+window.addEventListener('keydown', start_audio);
+
+// sounds of each kind started per frame, past that it's just noise (and synthesizing
+// them would make us miss the frame)
+const max_per_kind = 3;
+let playing = 0; // sounds still ringing
+let tink = null; // every arrival sounds the same, so it's only synthesized once
+
+const play_sounds = () => {
+	const events = world.drain_audio_events(); // always drain, or the queue just fills up
+	if (!audio) return;
+
+	const stride = 8; // AudioEvent::STRIDE, numbers per event
+	const started = [0, 0, 0, 0, 0]; // by kind
+	for (let i = 0; i < events.length; i += stride) {
+		const kind = events[i + 1];
+		if (started[kind]++ >= max_per_kind) continue;
+
+		let buffer = kind == 4 ? tink : null;
+		if (!buffer) {
+			const samples = synth.render_event(events.subarray(i, i + stride));
+			if (samples.length == 0) continue;
+			buffer = audio.createBuffer(1, samples.length, audio.sampleRate);
+			buffer.copyToChannel(samples, 0);
+			if (kind == 4) tink = buffer;
+		}
+
+		const source = audio.createBufferSource();
+		source.buffer = buffer;
+		// the more sounds are already ringing, the quieter a new one starts, so a pile
+		// of them doesn't clip
+		const gain = audio.createGain();
+		gain.gain.value = 1 / Math.sqrt(++playing);
+		source.onended = () => playing--;
+		const pan = audio.createStereoPanner();
+		pan.pan.value = Math.max(-1, Math.min(1, events[i + 2] / world.world_width() * 2 - 1));
+		source.connect(gain).connect(pan).connect(audio.destination);
+		source.start();
+	}
+};
+
 let focused = true;
-let lastTime = performance.now();
 const loop = () => {
 	if (focused)
 		setTimeout(loop, mspf);
 	const now = performance.now();
-	const dt = (now - lastTime) / 1000;
 
-	// measure(() => world.loopity_loop(now, dt));
-	world.loopity_loop(now, dt);
+	// measure(() => world.loopity_loop(now));
+	world.loopity_loop(now);
+	if (pixels)
+		blit();
+	play_sounds();
 
-	lastTime = performance.now();
+	// lets the world scale itself down when we can't keep up
+	world.report_frame_time(performance.now() - now);
 };
 
 window.addEventListener('focus', () => {
 	focused = true;
-	lastTime = performance.now();
 	setTimeout(loop, mspf);
 });
 window.addEventListener('blur', () => {